@@ -3,21 +3,43 @@
 #[allow(unused)]
 
 /// user app's stack size
-pub const USER_STACK_SIZE: usize = 4096;
+pub const USER_STACK_SIZE: usize = 4096 * 2;
+/// the room reserved for the heap of a user app, between its image and its stack
+pub const USER_HEAP_SIZE: usize = 0x40_0000;
 /// kernel stack size
 pub const KERNEL_STACK_SIZE: usize = 4096 * 2;
-/// kernel heap size
-pub const KERNEL_HEAP_SIZE: usize = 0x20000;
-/// the max number of apps
-pub const MAX_APP_NUM: usize = 16;
-/// base_addr(changed) of app
-pub const APP_BASE_ADDRESS: usize = 0x80400000;
-/// size limit of app
-pub const APP_SIZE_LIMIT: usize = 0x20000;
+/// kernel heap size at boot
+pub const KERNEL_HEAP_SIZE: usize = 0x30_0000;
+/// the size the kernel heap may grow to, with frames taken from the frame allocator
+pub const KERNEL_HEAP_MAX_SIZE: usize = 0x100_0000;
 
+/// page size : 4KB
+pub const PAGE_SIZE: usize = 0x1000;
+/// page size bits: 12
+pub const PAGE_SIZE_BITS: usize = 0xc;
+/// the max length of a path passed by the user, including the ending `\0`
+pub const PATH_MAX: usize = PAGE_SIZE;
 /// the max number of syscall
 pub const MAX_SYSCALL_NUM: usize = 500;
-/// clock frequency
+/// the stride of a task is BIG_STRIDE / priority
+pub const BIG_STRIDE: usize = 0x10_0000;
+/// the priority of a newly created task
+pub const DEFAULT_PRIORITY: usize = 16;
+/// the virtual addr of trapoline
+pub const TRAMPOLINE: usize = usize::MAX - PAGE_SIZE + 1;
+/// the virtual addr of trap context
+pub const TRAP_CONTEXT_BASE: usize = TRAMPOLINE - PAGE_SIZE;
+/// clock frequency, unless the device tree tells otherwise
 pub const CLOCK_FREQ: usize = 12500000;
-/// the physical memory end
+/// the physical memory end, unless the device tree tells otherwise
 pub const MEMORY_END: usize = 0x88000000;
+
+/// the `sifive_test` device of the QEMU virt machine, used to power off
+pub const VIRT_TEST: usize = 0x10_0000;
+
+/// The memory-mapped I/O regions identically mapped into kernel space
+/// when there is no device tree
+pub const MMIO: &[(usize, usize)] = &[
+    (VIRT_TEST, 0x00_2000), // VIRT_TEST/RTC  in virt machine
+];
+
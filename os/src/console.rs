@@ -1,6 +1,14 @@
-//! SBI console driver, for text output
-use crate::sbi::console_putchar;
+//! SBI console driver, for text output and input
+use crate::fdt::clock_freq;
+use crate::sbi::{console_getchar, console_putchar};
+use crate::sync::UPSafeCell;
+use crate::task::{wakeup_task, TaskControlBlock};
+use crate::timer::{add_kernel_timer, get_time};
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
 use core::fmt::{self, Write};
+use lazy_static::*;
 
 struct Stdout;
 
@@ -17,6 +25,13 @@ pub fn print(args: fmt::Arguments) {
     Stdout.write_fmt(args).unwrap();
 }
 
+/// Write raw bytes to the console, which need not be UTF-8
+pub fn write_bytes(bytes: &[u8]) {
+    for &c in bytes {
+        console_putchar(c as usize);
+    }
+}
+
 /// Print! to the host console using the format string and arguments.
 #[macro_export]
 macro_rules! print {
@@ -32,3 +47,70 @@ macro_rules! println {
         $crate::console::print(format_args!(concat!($fmt, "\n") $(, $($arg)+)?))
     }
 }
+
+/// How often console input is polled while some task is waiting for it, in timer ticks
+fn stdin_poll_interval() -> usize {
+    clock_freq() / 100
+}
+
+/// Console input which has arrived but not been read yet, and the tasks waiting for it
+struct Stdin {
+    buffer: VecDeque<u8>,
+    waiters: VecDeque<Arc<TaskControlBlock>>,
+    /// whether a timer to poll the input is pending
+    polling: bool,
+}
+
+lazy_static! {
+    static ref STDIN: UPSafeCell<Stdin> = unsafe {
+        UPSafeCell::new(Stdin {
+            buffer: VecDeque::new(),
+            waiters: VecDeque::new(),
+            polling: false,
+        })
+    };
+}
+
+/// Fetch the pending console input through SBI, and wake up the tasks waiting for it.
+///
+/// SBI gives no interrupt for console input, so it is polled by a timer while some task waits.
+pub fn poll_stdin() {
+    let mut stdin = STDIN.exclusive_access();
+    while let Some(c) = console_getchar() {
+        stdin.buffer.push_back(c);
+    }
+    if stdin.buffer.is_empty() {
+        return;
+    }
+    let waiters: VecDeque<_> = stdin.waiters.drain(..).collect();
+    drop(stdin);
+    for task in waiters {
+        wakeup_task(task);
+    }
+}
+
+/// Poll console input, and keep polling as long as some task waits for it
+fn poll_stdin_timer() {
+    poll_stdin();
+    let mut stdin = STDIN.exclusive_access();
+    stdin.polling = !stdin.waiters.is_empty();
+    if stdin.polling {
+        add_kernel_timer(get_time() + stdin_poll_interval(), poll_stdin_timer);
+    }
+}
+
+/// Take at most `len` bytes of console input, or register `task` as a waiter
+/// and return `None` if there is none.
+pub fn read_stdin(len: usize, task: &Arc<TaskControlBlock>) -> Option<Vec<u8>> {
+    let mut stdin = STDIN.exclusive_access();
+    if stdin.buffer.is_empty() {
+        stdin.waiters.push_back(task.clone());
+        if !stdin.polling {
+            stdin.polling = true;
+            add_kernel_timer(get_time() + stdin_poll_interval(), poll_stdin_timer);
+        }
+        return None;
+    }
+    let len = len.min(stdin.buffer.len());
+    Some(stdin.buffer.drain(..len).collect())
+}
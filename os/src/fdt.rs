@@ -0,0 +1,382 @@
+//! Flattened device tree parsing
+//!
+//! The SBI passes the physical address of a flattened device tree (FDT) blob
+//! in `a1` when it jumps to the kernel. [`init()`] parses it once at boot,
+//! before the frame allocator hands out the memory holding it, and keeps what
+//! the kernel needs in [`MachineInfo`]. Without a valid device tree, the
+//! values in [`crate::config`] are used.
+//!
+//! See the Devicetree Specification, chapter 5, for the format.
+
+use crate::config::{CLOCK_FREQ, MEMORY_END, MMIO, VIRT_TEST};
+use crate::sync::UPSafeCell;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicUsize, Ordering};
+use lazy_static::*;
+
+const FDT_MAGIC: u32 = 0xd00d_feed;
+const FDT_BEGIN_NODE: u32 = 1;
+const FDT_END_NODE: u32 = 2;
+const FDT_PROP: u32 = 3;
+const FDT_NOP: u32 = 4;
+const FDT_END: u32 = 9;
+
+/// The compatible strings of the devices the kernel drives through MMIO,
+/// only these are mapped into kernel space
+const SUPPORTED_DEVICES: &[&str] = &["sifive,test0", "ns16550a", "google,goldfish-rtc"];
+
+/// A device node with registers
+#[derive(Clone, Debug)]
+pub struct Device {
+    /// Node name, e.g. `uart@10000000`
+    pub name: String,
+    /// The `compatible` strings, most specific first
+    pub compatible: Vec<String>,
+    /// Physical address of the first register block
+    pub base: usize,
+    /// Size of the first register block
+    pub size: usize,
+}
+
+/// What the kernel knows about the machine it runs on
+#[derive(Clone, Debug)]
+pub struct MachineInfo {
+    /// Whether it comes from a device tree rather than [`crate::config`]
+    pub device_tree: bool,
+    /// Start of the first RAM region
+    pub memory_start: usize,
+    /// End of the first RAM region
+    pub memory_end: usize,
+    /// Frequency of the `time` CSR
+    pub clock_freq: usize,
+    /// Number of harts
+    pub hart_count: usize,
+    /// Kernel command line from `/chosen`
+    pub bootargs: String,
+    /// Devices with registers
+    pub devices: Vec<Device>,
+}
+
+impl MachineInfo {
+    /// The machine described by [`crate::config`], used if there is no device
+    /// tree. It must not allocate, as it may be needed before the heap is set up.
+    fn fallback() -> Self {
+        Self {
+            device_tree: false,
+            memory_start: 0x8000_0000,
+            memory_end: MEMORY_END,
+            clock_freq: CLOCK_FREQ,
+            hart_count: 1,
+            bootargs: String::new(),
+            devices: Vec::new(),
+        }
+    }
+    /// The first device compatible with `compatible`
+    fn find_device(&self, compatible: &str) -> Option<&Device> {
+        self.devices
+            .iter()
+            .find(|device| device.compatible.iter().any(|c| c == compatible))
+    }
+}
+
+lazy_static! {
+    /// MACHINE instance through lazy_static!
+    static ref MACHINE: UPSafeCell<MachineInfo> =
+        unsafe { UPSafeCell::new(MachineInfo::fallback()) };
+}
+
+/// The base of the `sifive_test` device, 0 if there is none. Kept apart from
+/// [`MACHINE`], so that shutting down on a panic never borrows it, whether the
+/// panic happened while it was borrowed or before the device tree was parsed.
+static VIRT_TEST_BASE: AtomicUsize = AtomicUsize::new(VIRT_TEST);
+/// [`MachineInfo::memory_end`], kept apart from [`MACHINE`] as it is read
+/// without borrowing it
+static MEMORY_END_ADDR: AtomicUsize = AtomicUsize::new(MEMORY_END);
+/// [`MachineInfo::clock_freq`], kept apart from [`MACHINE`] as it is read on
+/// every reading of the time
+static CLOCK_FREQ_HZ: AtomicUsize = AtomicUsize::new(CLOCK_FREQ);
+
+/// A node of the device tree, borrowing property values from the blob
+struct Node {
+    name: &'static str,
+    props: Vec<(&'static str, &'static [u8])>,
+    children: Vec<Node>,
+}
+
+impl Node {
+    fn prop(&self, name: &str) -> Option<&'static [u8]> {
+        self.props
+            .iter()
+            .find(|(prop, _)| *prop == name)
+            .map(|&(_, value)| value)
+    }
+    fn prop_u32(&self, name: &str) -> Option<u32> {
+        self.prop(name).and_then(|value| be32(value, 0))
+    }
+    fn prop_str(&self, name: &str) -> Option<&'static str> {
+        self.prop(name)
+            .and_then(|value| core::str::from_utf8(value).ok())
+            .map(|s| s.trim_end_matches('\0'))
+    }
+    fn child(&self, name: &str) -> Option<&Node> {
+        self.children.iter().find(|child| child.name == name)
+    }
+    /// The first `(address, size)` pair of `reg`, encoded with the cells of the parent
+    fn reg(&self, address_cells: usize, size_cells: usize) -> Option<(usize, usize)> {
+        let value = self.prop("reg")?;
+        let address = be_cells(value, 0, address_cells)?;
+        let size = be_cells(value, address_cells * 4, size_cells)?;
+        Some((address, size))
+    }
+    fn address_cells(&self) -> usize {
+        self.prop_u32("#address-cells").unwrap_or(2) as usize
+    }
+    fn size_cells(&self) -> usize {
+        self.prop_u32("#size-cells").unwrap_or(1) as usize
+    }
+}
+
+/// Read a big-endian u32 at `offset`
+fn be32(bytes: &[u8], offset: usize) -> Option<u32> {
+    let bytes = bytes.get(offset..offset.checked_add(4)?)?;
+    Some(u32::from_be_bytes(bytes.try_into().unwrap()))
+}
+
+/// Read a big-endian number of `cells` u32 at `offset`
+fn be_cells(bytes: &[u8], offset: usize, cells: usize) -> Option<usize> {
+    (0..cells).try_fold(0usize, |value, i| {
+        Some(value.checked_shl(32).unwrap_or(0) | be32(bytes, offset + i * 4)? as usize)
+    })
+}
+
+/// Read a string ending with `\0` at `offset`
+fn c_str(bytes: &'static [u8], offset: usize) -> Option<&'static str> {
+    let bytes = bytes.get(offset..)?;
+    let len = bytes.iter().position(|&c| c == 0)?;
+    core::str::from_utf8(&bytes[..len]).ok()
+}
+
+/// Parser of the structure block of a device tree
+struct Parser {
+    structs: &'static [u8],
+    strings: &'static [u8],
+    offset: usize,
+}
+
+impl Parser {
+    /// Next token, skipping NOPs
+    fn token(&mut self) -> Option<u32> {
+        loop {
+            let token = be32(self.structs, self.offset)?;
+            self.offset += 4;
+            if token != FDT_NOP {
+                return Some(token);
+            }
+        }
+    }
+    /// Parse a node whose FDT_BEGIN_NODE has been consumed
+    fn node(&mut self, depth: usize) -> Option<Node> {
+        // malformed blobs must not overflow the kernel stack
+        if depth > 16 {
+            return None;
+        }
+        let name = c_str(self.structs, self.offset)?;
+        self.offset = (self.offset + name.len() + 1 + 3) & !3;
+        let mut node = Node {
+            name,
+            props: Vec::new(),
+            children: Vec::new(),
+        };
+        loop {
+            match self.token()? {
+                FDT_PROP => {
+                    let len = be32(self.structs, self.offset)? as usize;
+                    let name_offset = be32(self.structs, self.offset + 4)? as usize;
+                    let start = self.offset + 8;
+                    let value = self.structs.get(start..start.checked_add(len)?)?;
+                    node.props.push((c_str(self.strings, name_offset)?, value));
+                    self.offset = (start + len + 3) & !3;
+                }
+                FDT_BEGIN_NODE => {
+                    let child = self.node(depth + 1)?;
+                    node.children.push(child);
+                }
+                FDT_END_NODE => return Some(node),
+                _ => return None,
+            }
+        }
+    }
+}
+
+/// Parse the device tree blob at `dtb_pa` into its root node
+fn parse(dtb_pa: usize) -> Option<Node> {
+    if dtb_pa == 0 || dtb_pa % 4 != 0 {
+        return None;
+    }
+    let header = unsafe { core::slice::from_raw_parts(dtb_pa as *const u8, 40) };
+    if be32(header, 0)? != FDT_MAGIC {
+        return None;
+    }
+    let total_size = be32(header, 4)? as usize;
+    let blob = unsafe { core::slice::from_raw_parts(dtb_pa as *const u8, total_size) };
+    let off_dt_struct = be32(header, 8)? as usize;
+    let off_dt_strings = be32(header, 12)? as usize;
+    let size_dt_strings = be32(header, 32)? as usize;
+    let size_dt_struct = be32(header, 36)? as usize;
+    let mut parser = Parser {
+        structs: blob.get(off_dt_struct..off_dt_struct.checked_add(size_dt_struct)?)?,
+        strings: blob.get(off_dt_strings..off_dt_strings.checked_add(size_dt_strings)?)?,
+        offset: 0,
+    };
+    if parser.token()? != FDT_BEGIN_NODE {
+        return None;
+    }
+    let root = parser.node(0)?;
+    if parser.token()? != FDT_END {
+        return None;
+    }
+    Some(root)
+}
+
+/// Collect the devices with registers under `node`, whose `reg` uses the given cells
+fn collect_devices(node: &Node, address_cells: usize, size_cells: usize, out: &mut Vec<Device>) {
+    for child in node.children.iter() {
+        if let (Some(compatible), Some((base, size))) = (
+            child.prop("compatible"),
+            child.reg(address_cells, size_cells),
+        ) {
+            let compatible = compatible
+                .split(|&c| c == 0)
+                .filter(|s| !s.is_empty())
+                .filter_map(|s| core::str::from_utf8(s).ok())
+                .map(String::from)
+                .collect();
+            out.push(Device {
+                name: String::from(child.name),
+                compatible,
+                base,
+                size,
+            });
+        }
+        collect_devices(child, child.address_cells(), child.size_cells(), out);
+    }
+}
+
+/// Extract what the kernel needs from the root node
+fn machine_info(root: &Node) -> MachineInfo {
+    let mut info = MachineInfo::fallback();
+    info.device_tree = true;
+    let (address_cells, size_cells) = (root.address_cells(), root.size_cells());
+    if let Some((start, size)) = root
+        .children
+        .iter()
+        .filter(|node| node.prop_str("device_type") == Some("memory"))
+        .find_map(|node| node.reg(address_cells, size_cells))
+    {
+        info.memory_start = start;
+        info.memory_end = start + size;
+    }
+    if let Some(cpus) = root.child("cpus") {
+        let harts = cpus
+            .children
+            .iter()
+            .filter(|node| node.prop_str("device_type") == Some("cpu"));
+        info.hart_count = harts.clone().count().max(1);
+        // usually in /cpus, but may be in each cpu node
+        if let Some(freq) = cpus.prop_u32("timebase-frequency").or_else(|| {
+            harts
+                .clone()
+                .find_map(|cpu| cpu.prop_u32("timebase-frequency"))
+        }) {
+            info.clock_freq = freq as usize;
+        }
+    }
+    if let Some(bootargs) = root
+        .child("chosen")
+        .and_then(|chosen| chosen.prop_str("bootargs"))
+    {
+        info.bootargs = String::from(bootargs);
+    }
+    let mut devices = Vec::new();
+    collect_devices(root, address_cells, size_cells, &mut devices);
+    info.devices = devices;
+    info
+}
+
+/// Parse the device tree passed by the SBI at `dtb_pa`, falling back to
+/// [`crate::config`] if it is missing or malformed.
+///
+/// Must be called before the frame allocator is initialized, as the blob lies
+/// in memory the frame allocator hands out.
+pub fn init(hart_id: usize, dtb_pa: usize) {
+    let Some(root) = parse(dtb_pa) else {
+        warn!(
+            "[kernel] no valid device tree at {:#x}, using the default configuration",
+            dtb_pa
+        );
+        return;
+    };
+    let info = machine_info(&root);
+    info!(
+        "[kernel] hart {} of {}, memory [{:#x}, {:#x}), timebase {} Hz, bootargs \"{}\"",
+        hart_id,
+        info.hart_count,
+        info.memory_start,
+        info.memory_end,
+        info.clock_freq,
+        info.bootargs
+    );
+    for device in info.devices.iter() {
+        debug!(
+            "[kernel] device {} {:?} [{:#x}, +{:#x})",
+            device.name, device.compatible, device.base, device.size
+        );
+    }
+    let virt_test = info
+        .find_device("sifive,test0")
+        .map_or(0, |device| device.base);
+    VIRT_TEST_BASE.store(virt_test, Ordering::Relaxed);
+    MEMORY_END_ADDR.store(info.memory_end, Ordering::Relaxed);
+    CLOCK_FREQ_HZ.store(info.clock_freq, Ordering::Relaxed);
+    *MACHINE.exclusive_access() = info;
+}
+
+/// The end of RAM
+pub fn memory_end() -> usize {
+    MEMORY_END_ADDR.load(Ordering::Relaxed)
+}
+
+/// The frequency of the `time` CSR
+pub fn clock_freq() -> usize {
+    CLOCK_FREQ_HZ.load(Ordering::Relaxed)
+}
+
+/// The base of the QEMU `sifive_test` device used to power off, if any.
+/// Safe to call on the panic path.
+pub fn virt_test_base() -> Option<usize> {
+    match VIRT_TEST_BASE.load(Ordering::Relaxed) {
+        0 => None,
+        base => Some(base),
+    }
+}
+
+/// The register blocks to map into kernel space, those of the devices the
+/// kernel drives, or [`MMIO`] without a device tree
+pub fn mmio_regions() -> Vec<(usize, usize)> {
+    let machine = MACHINE.exclusive_access();
+    if !machine.device_tree {
+        return MMIO.to_vec();
+    }
+    machine
+        .devices
+        .iter()
+        .filter(|device| {
+            device
+                .compatible
+                .iter()
+                .any(|c| SUPPORTED_DEVICES.contains(&c.as_str()))
+        })
+        .map(|device| (device.base, device.size))
+        .collect()
+}
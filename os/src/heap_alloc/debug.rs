@@ -0,0 +1,239 @@
+//! Kernel heap diagnostics, enabled by the `heap_debug` feature
+//!
+//! Every allocation is laid out as
+//!
+//! ```text
+//! | padding | Header | front red zone | data | back red zone |
+//!                                      ^ returned pointer
+//! ```
+//!
+//! The data is filled with [`ALLOC_POISON`] on allocation, and the whole block
+//! with [`FREE_POISON`] when it is freed, so that reads of uninitialized or
+//! freed memory stand out. The red zones are checked when the block is freed,
+//! and when the live allocations are dumped.
+//!
+//! Live allocations are recorded in a fixed table, together with the return
+//! addresses of their callers found by walking the frame pointers, so that
+//! the table itself never allocates.
+//!
+//! All the functions must be called with the heap lock held, which serializes
+//! the accesses to the table.
+
+use crate::config::{KERNEL_STACK_SIZE, PAGE_SIZE, TRAMPOLINE};
+use crate::task::kernel_stack_position;
+use core::alloc::Layout;
+use core::arch::asm;
+
+/// Fills the data of a new allocation
+const ALLOC_POISON: u8 = 0xcd;
+/// Fills a freed block
+const FREE_POISON: u8 = 0xdd;
+/// Fills the red zones
+const RED_ZONE_POISON: u8 = 0xfd;
+/// The size of each red zone
+const RED_ZONE: usize = 16;
+/// The number of allocations which can be recorded at once
+const MAX_TRACKED: usize = 2048;
+/// The number of callers recorded for each allocation
+const CALLER_DEPTH: usize = 4;
+/// The frames of the allocator itself, skipped when recording callers
+const SKIP_FRAMES: usize = 2;
+/// `Header::slot` of an allocation not in the table
+const UNTRACKED: usize = usize::MAX;
+
+/// Written just before the front red zone
+#[repr(C)]
+struct Header {
+    /// the index in the table, or [`UNTRACKED`]
+    slot: usize,
+    /// the size requested
+    size: usize,
+}
+
+/// A live allocation
+#[derive(Clone, Copy)]
+struct Record {
+    /// the returned pointer, 0 for a free slot
+    ptr: usize,
+    size: usize,
+    callers: [usize; CALLER_DEPTH],
+}
+
+/// The live allocations
+struct Table {
+    records: [Record; MAX_TRACKED],
+    /// where to start looking for a free slot
+    hint: usize,
+    live: usize,
+    peak: usize,
+    /// allocations which did not fit in the table
+    untracked: usize,
+}
+
+const EMPTY_RECORD: Record = Record {
+    ptr: 0,
+    size: 0,
+    callers: [0; CALLER_DEPTH],
+};
+
+static mut TABLE: Table = Table {
+    records: [EMPTY_RECORD; MAX_TRACKED],
+    hint: 0,
+    live: 0,
+    peak: 0,
+    untracked: 0,
+};
+
+/// The distance from the start of the block to the data
+fn front_len(layout: Layout) -> usize {
+    (core::mem::size_of::<Header>() + RED_ZONE).next_multiple_of(layout.align())
+}
+
+/// The layout of the block holding an allocation of `layout` with its red zones
+pub fn raw_layout(layout: Layout) -> Layout {
+    let size = front_len(layout) + layout.size() + RED_ZONE;
+    Layout::from_size_align(size, layout.align().max(core::mem::align_of::<Header>())).unwrap()
+}
+
+/// The `[bottom, top)` of the kernel stack holding `sp`: the boot stack, or
+/// the kernel stack of a task below the trampoline
+fn stack_bounds(sp: usize) -> (usize, usize) {
+    extern "C" {
+        fn boot_stack_lower_bound();
+        fn boot_stack_top();
+    }
+    let boot_stack = boot_stack_lower_bound as usize..boot_stack_top as usize;
+    if boot_stack.contains(&sp) {
+        return (boot_stack.start, boot_stack.end);
+    }
+    kernel_stack_position((TRAMPOLINE - sp) / (KERNEL_STACK_SIZE + PAGE_SIZE))
+}
+
+/// The return addresses of the callers, by walking the frame pointers
+fn callers() -> [usize; CALLER_DEPTH] {
+    let mut callers = [0; CALLER_DEPTH];
+    let (mut fp, sp): (usize, usize);
+    unsafe {
+        asm!("mv {}, s0", out(reg) fp);
+        asm!("mv {}, sp", out(reg) sp);
+    }
+    // a frame pointer saved by a trap from user mode is whatever the user
+    // left in s0, never follow one out of the current stack
+    let (bottom, top) = stack_bounds(sp);
+    for i in 0..SKIP_FRAMES + CALLER_DEPTH {
+        if fp % 8 != 0 || fp < bottom + 16 || fp > top {
+            break;
+        }
+        // the return address and the frame pointer of the caller are saved
+        // just below the frame pointer
+        let (ra, prev_fp) = unsafe { (*(fp as *const usize).sub(1), *(fp as *const usize).sub(2)) };
+        if i >= SKIP_FRAMES {
+            callers[i - SKIP_FRAMES] = ra;
+        }
+        // the stack grows down, anything else is not a frame
+        if prev_fp <= fp {
+            break;
+        }
+        fp = prev_fp;
+    }
+    callers
+}
+
+/// Whether both red zones of the allocation at `ptr` are intact
+unsafe fn red_zones_intact(ptr: *mut u8, size: usize) -> bool {
+    let front = core::slice::from_raw_parts(ptr.sub(RED_ZONE), RED_ZONE);
+    let back = core::slice::from_raw_parts(ptr.add(size), RED_ZONE);
+    front
+        .iter()
+        .chain(back.iter())
+        .all(|&b| b == RED_ZONE_POISON)
+}
+
+/// Set up the block at `raw`, allocated with [`raw_layout`] of `layout`,
+/// record it, and return the pointer to hand out
+pub unsafe fn track_alloc(raw: *mut u8, layout: Layout) -> *mut u8 {
+    let ptr = raw.add(front_len(layout));
+    let size = layout.size();
+    ptr.sub(RED_ZONE).write_bytes(RED_ZONE_POISON, RED_ZONE);
+    ptr.write_bytes(ALLOC_POISON, size);
+    ptr.add(size).write_bytes(RED_ZONE_POISON, RED_ZONE);
+
+    let table = &mut *core::ptr::addr_of_mut!(TABLE);
+    let slot = (0..MAX_TRACKED)
+        .map(|i| (table.hint + i) % MAX_TRACKED)
+        .find(|&i| table.records[i].ptr == 0);
+    match slot {
+        Some(slot) => {
+            table.records[slot] = Record {
+                ptr: ptr as usize,
+                size,
+                callers: callers(),
+            };
+            table.hint = slot + 1;
+        }
+        None => table.untracked += 1,
+    }
+    table.live += 1;
+    table.peak = table.peak.max(table.live);
+    let header = ptr.sub(RED_ZONE + core::mem::size_of::<Header>()) as *mut Header;
+    header.write(Header {
+        slot: slot.unwrap_or(UNTRACKED),
+        size,
+    });
+    ptr
+}
+
+/// Check and forget the allocation at `ptr` of `layout`, poison it, and
+/// return the block to free with its layout
+pub unsafe fn track_dealloc(ptr: *mut u8, layout: Layout) -> (*mut u8, Layout) {
+    let header = ptr.sub(RED_ZONE + core::mem::size_of::<Header>()) as *mut Header;
+    let Header { slot, size } = header.read();
+    let table = &mut *core::ptr::addr_of_mut!(TABLE);
+    if size != layout.size() || (slot != UNTRACKED && table.records[slot].ptr != ptr as usize) {
+        panic!(
+            "[kernel] heap: bad free of {:p}, layout = {:?}, double free or not allocated",
+            ptr, layout
+        );
+    }
+    if !red_zones_intact(ptr, size) {
+        let callers = if slot == UNTRACKED {
+            [0; CALLER_DEPTH]
+        } else {
+            table.records[slot].callers
+        };
+        panic!(
+            "[kernel] heap: red zone of {:p} ({} bytes) overwritten, allocated from {:x?}",
+            ptr, size, callers
+        );
+    }
+    if slot != UNTRACKED {
+        table.records[slot] = EMPTY_RECORD;
+    }
+    table.live -= 1;
+    let raw = ptr.sub(front_len(layout));
+    let raw_layout = raw_layout(layout);
+    raw.write_bytes(FREE_POISON, raw_layout.size());
+    (raw, raw_layout)
+}
+
+/// Print the live allocations and where they come from, checking their red zones
+pub fn dump_live_allocations() {
+    let table = unsafe { &*core::ptr::addr_of!(TABLE) };
+    println!(
+        "[kernel] heap: {} live allocations, {} peak, {} untracked",
+        table.live, table.peak, table.untracked
+    );
+    let mut bytes = 0;
+    for record in table.records.iter().filter(|record| record.ptr != 0) {
+        bytes += record.size;
+        let intact = unsafe { red_zones_intact(record.ptr as *mut u8, record.size) };
+        println!(
+            "[kernel] heap: {:#x} {} bytes from {:x?}{}",
+            record.ptr,
+            record.size,
+            record.callers,
+            if intact { "" } else { " RED ZONE OVERWRITTEN" }
+        );
+    }
+    println!("[kernel] heap: {} bytes live in tracked allocations", bytes);
+}
@@ -0,0 +1,193 @@
+//! The global allocator
+//!
+//! The kernel heap starts with a static array, and grows on demand with
+//! contiguous frames from the frame allocator, up to `KERNEL_HEAP_MAX_SIZE`.
+//! Frames taken by the heap are never given back.
+//!
+//! With the `heap_debug` feature, allocations are also checked and tracked,
+//! see [`debug`].
+
+#[cfg(feature = "heap_debug")]
+mod debug;
+
+use crate::config::{KERNEL_HEAP_MAX_SIZE, KERNEL_HEAP_SIZE, PAGE_SIZE};
+use crate::mm::{frame_alloc_contiguous, PhysAddr};
+use buddy_system_allocator::{Heap, LockedHeap};
+use core::alloc::{GlobalAlloc, Layout};
+use core::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "heap_debug")]
+use debug::{raw_layout, track_alloc, track_dealloc};
+
+/// Without `heap_debug`, allocations are handed out as they are
+#[cfg(not(feature = "heap_debug"))]
+fn raw_layout(layout: Layout) -> Layout {
+    layout
+}
+
+#[cfg(not(feature = "heap_debug"))]
+unsafe fn track_alloc(raw: *mut u8, _layout: Layout) -> *mut u8 {
+    raw
+}
+
+#[cfg(not(feature = "heap_debug"))]
+unsafe fn track_dealloc(ptr: *mut u8, layout: Layout) -> (*mut u8, Layout) {
+    (ptr, layout)
+}
+
+/// The least the heap grows by at once, to keep the number of regions low
+const HEAP_GROW_MIN: usize = 0x4_0000;
+
+/// Warn when the heap has grown beyond this percentage of its cap
+const HEAP_WARN_PERCENT: usize = 90;
+
+/// The kernel heap, growing when an allocation fails
+struct GrowableHeap {
+    heap: LockedHeap,
+    /// bytes added to the heap after boot
+    grown: AtomicUsize,
+}
+
+impl GrowableHeap {
+    /// Add enough frames to `heap` to satisfy `layout`, returns whether it grew
+    fn grow(&self, heap: &mut Heap, layout: &Layout) -> bool {
+        // a buddy allocator can carve an aligned block of a power of two out of
+        // any region twice as large
+        let need = layout.size().max(layout.align()).next_power_of_two() * 2;
+        let size = need.max(HEAP_GROW_MIN).next_multiple_of(PAGE_SIZE);
+        let grown = self.grown.load(Ordering::Relaxed);
+        if KERNEL_HEAP_SIZE + grown + size > KERNEL_HEAP_MAX_SIZE {
+            return false;
+        }
+        let Some(ppn) = frame_alloc_contiguous(size / PAGE_SIZE) else {
+            return false;
+        };
+        // identically mapped in kernel space
+        let start: PhysAddr = ppn.into();
+        unsafe {
+            heap.add_to_heap(start.0, start.0 + size);
+        }
+        self.grown.store(grown + size, Ordering::Relaxed);
+        let total = KERNEL_HEAP_SIZE + grown + size;
+        if total * 100 >= KERNEL_HEAP_MAX_SIZE * HEAP_WARN_PERCENT {
+            warn!(
+                "[kernel] heap grown to {:#x} bytes, close to its cap {:#x}",
+                total, KERNEL_HEAP_MAX_SIZE
+            );
+        } else {
+            debug!("[kernel] heap grown to {:#x} bytes", total);
+        }
+        true
+    }
+}
+
+unsafe impl GlobalAlloc for GrowableHeap {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let mut heap = self.heap.lock();
+        let raw = raw_layout(layout);
+        let ptr = match heap.alloc(raw) {
+            Ok(ptr) => ptr,
+            Err(()) if self.grow(&mut heap, &raw) => match heap.alloc(raw) {
+                Ok(ptr) => ptr,
+                Err(()) => return core::ptr::null_mut(),
+            },
+            Err(()) => return core::ptr::null_mut(),
+        };
+        track_alloc(ptr.as_ptr(), layout)
+    }
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let mut heap = self.heap.lock();
+        let (raw, raw_layout) = track_dealloc(ptr, layout);
+        heap.dealloc(core::ptr::NonNull::new_unchecked(raw), raw_layout);
+    }
+}
+
+#[global_allocator]
+/// heap allocator instance
+static HEAP_ALLOCATOR: GrowableHeap = GrowableHeap {
+    heap: LockedHeap::empty(),
+    grown: AtomicUsize::new(0),
+};
+
+/// heap space ([u8; KERNEL_HEAP_SIZE])
+static mut HEAP_SPACE: [u8; KERNEL_HEAP_SIZE] = [0; KERNEL_HEAP_SIZE];
+
+/// initiate heap allocator
+pub fn init_heap() {
+    unsafe {
+        HEAP_ALLOCATOR
+            .heap
+            .lock()
+            .init(HEAP_SPACE.as_ptr() as usize, KERNEL_HEAP_SIZE);
+    }
+}
+
+/// Usage statistics of the kernel heap, in bytes
+#[derive(Clone, Copy, Debug)]
+pub struct HeapStats {
+    /// bytes managed by the heap
+    pub total: usize,
+    /// bytes requested by the allocations alive
+    pub user: usize,
+    /// bytes taken by the allocations alive, rounded up to powers of two
+    pub actual: usize,
+    /// bytes added after boot
+    pub grown: usize,
+    /// the size the heap may grow to
+    pub max: usize,
+}
+
+impl HeapStats {
+    /// Bytes lost to rounding allocations up, in percent of the allocated ones
+    pub fn fragmentation(&self) -> usize {
+        (self.actual - self.user) * 100 / self.actual.max(1)
+    }
+    /// Bytes which can still be allocated, growing the heap if need be
+    pub fn available(&self) -> usize {
+        self.max - self.actual
+    }
+}
+
+/// The usage statistics of the kernel heap
+pub fn heap_stats() -> HeapStats {
+    let heap = HEAP_ALLOCATOR.heap.lock();
+    HeapStats {
+        total: heap.stats_total_bytes(),
+        user: heap.stats_alloc_user(),
+        actual: heap.stats_alloc_actual(),
+        grown: HEAP_ALLOCATOR.grown.load(Ordering::Relaxed),
+        max: KERNEL_HEAP_MAX_SIZE,
+    }
+}
+
+/// Print the usage statistics of the kernel heap
+pub fn print_heap_stats() {
+    let stats = heap_stats();
+    println!(
+        "[kernel] heap: {:#x} total ({:#x} grown, {:#x} max), {:#x} used, {:#x} allocated, {}% fragmentation, {:#x} available",
+        stats.total,
+        stats.grown,
+        stats.max,
+        stats.user,
+        stats.actual,
+        stats.fragmentation(),
+        stats.available()
+    );
+}
+
+/// Print the live allocations of the kernel heap and where they come from
+#[cfg(feature = "heap_debug")]
+pub fn dump_live_allocations() {
+    // serialize with the allocations
+    let _heap = HEAP_ALLOCATOR.heap.lock();
+    debug::dump_live_allocations();
+}
+
+#[alloc_error_handler]
+/// panic when heap allocation error occurs
+pub fn handle_alloc_error(layout: core::alloc::Layout) -> ! {
+    panic!(
+        "Heap allocation error, layout = {:?}, heap = {:?}",
+        layout,
+        heap_stats()
+    );
+}
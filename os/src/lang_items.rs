@@ -1,6 +1,6 @@
 //! The panic handler
 
-use crate::sbi::shutdown;
+use crate::sbi::{shutdown, ShutdownReason};
 use core::panic::PanicInfo;
 
 #[panic_handler]
@@ -16,5 +16,5 @@ fn panic(info: &PanicInfo) -> ! {
     } else {
         println!("[kernel] Panicked: {}", info.message().unwrap());
     }
-    shutdown()
+    shutdown(ShutdownReason::KernelPanic)
 }
@@ -6,13 +6,14 @@
 //! - [`trap`]: Handles all cases of switching from userspace to the kernel
 //! - [`task`]: Task management
 //! - [`syscall`]: System call handling and implementation
+//! - [`mm`]: Address map using SV39
 //!
 //! The operating system also starts in this module. Kernel code starts
 //! executing from `entry.asm`, after which [`rust_main()`] is called to
 //! initialize various pieces of functionality. (See its source code for
 //! details.)
 //!
-//! We then call [`task::run_first_task()`] and for the first time go to
+//! We then call [`task::run_tasks()`] and for the first time go to
 //! userspace.
 
 #![deny(missing_docs)]
@@ -25,15 +26,20 @@
 #[macro_use]
 extern crate log;
 
+#[macro_use]
+extern crate bitflags;
+
 extern crate alloc;
 
 #[macro_use]
 mod console;
 pub mod config;
+pub mod fdt;
 mod heap_alloc;
 pub mod lang_items;
 mod loader;
 pub mod logging;
+pub mod mm;
 pub mod sbi;
 pub mod sync;
 pub mod syscall;
@@ -93,15 +99,18 @@ fn kernel_log_info() {
 }
 
 #[no_mangle]
-/// the rust entry-point of os
-pub fn rust_main() -> ! {
+/// the rust entry-point of os, with the hart id and the device tree passed by the SBI
+pub fn rust_main(hart_id: usize, dtb_pa: usize) -> ! {
     clear_bss();
     kernel_log_info();
     heap_alloc::init_heap();
+    fdt::init(hart_id, dtb_pa);
+    mm::init();
+    mm::remap_test();
     trap::init();
-    loader::load_apps();
     trap::enable_timer_interrupt();
-    timer::set_next_trigger();
-    task::run_first_task();
+    loader::list_apps();
+    task::add_initproc();
+    task::run_tasks();
     panic!("Unreachable in rust_main!");
 }
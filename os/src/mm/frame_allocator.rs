@@ -0,0 +1,249 @@
+//! Implementation of [`FrameAllocator`] which
+//! controls all the frames in the operating system.
+
+use super::{PhysAddr, PhysPageNum};
+use crate::config::PAGE_SIZE;
+use crate::fdt::memory_end;
+use crate::sync::UPSafeCell;
+use core::fmt::{self, Debug, Formatter};
+use lazy_static::*;
+
+/// tracker for physical page frame allocation and deallocation
+pub struct FrameTracker {
+    /// physical page number
+    pub ppn: PhysPageNum,
+}
+
+impl FrameTracker {
+    /// Create a new FrameTracker
+    pub fn new(ppn: PhysPageNum) -> Self {
+        // page cleaning
+        let bytes_array = ppn.get_bytes_array();
+        for i in bytes_array {
+            *i = 0;
+        }
+        Self { ppn }
+    }
+}
+
+impl Debug for FrameTracker {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.write_fmt(format_args!("FrameTracker:PPN={:#x}", self.ppn.0))
+    }
+}
+
+impl Drop for FrameTracker {
+    fn drop(&mut self) {
+        frame_dealloc(self.ppn);
+    }
+}
+
+trait FrameAllocator {
+    fn new() -> Self;
+    fn alloc(&mut self) -> Option<PhysPageNum>;
+    fn dealloc(&mut self, ppn: PhysPageNum);
+}
+
+/// Usage statistics of the frame allocator, in frames
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FrameStats {
+    /// frames managed by the allocator
+    pub total: usize,
+    /// frames allocated now
+    pub allocated: usize,
+    /// the most frames ever allocated at once
+    pub peak: usize,
+    /// successful allocations of single frames
+    pub allocs: usize,
+    /// frames taken for good in contiguous runs, e.g. to grow the kernel heap
+    pub contiguous: usize,
+    /// deallocations
+    pub deallocs: usize,
+    /// allocations which failed for lack of memory
+    pub failures: usize,
+}
+
+impl FrameStats {
+    /// frames which can still be allocated
+    pub fn free(&self) -> usize {
+        self.total - self.allocated
+    }
+}
+
+/// The header written at the start of a frame in the free list
+#[repr(C)]
+struct FreeFrame {
+    /// the next free frame, 0 at the end of the list
+    next: usize,
+}
+
+/// An implementation for frame allocator, handing out the never used frames
+/// in order, and recycling freed frames through a list linked inside them,
+/// so that it needs no kernel heap.
+///
+/// Which frames are in the list is recorded in a bitmap, kept in the first
+/// frames of the range, as the content of a frame being freed is up to its
+/// owner and proves nothing.
+pub struct FreeListFrameAllocator {
+    /// the first frame of the bitmap, one bit per frame from `start`
+    bitmap: usize,
+    start: usize,
+    current: usize,
+    end: usize,
+    /// the most recently freed frame, 0 if none
+    free_head: usize,
+    stats: FrameStats,
+}
+
+impl FreeListFrameAllocator {
+    /// Initialize the allocator with the physical page range `[l, r)`
+    pub fn init(&mut self, l: PhysPageNum, r: PhysPageNum) {
+        let bitmap_frames = (r.0 - l.0).div_ceil(PAGE_SIZE * 8);
+        for ppn in l.0..l.0 + bitmap_frames {
+            PhysPageNum::from(ppn).get_bytes_array().fill(0);
+        }
+        self.bitmap = l.0;
+        self.start = l.0 + bitmap_frames;
+        self.current = self.start;
+        self.end = r.0;
+        self.stats.total = self.end - self.current;
+        trace!("last {} Physical Frames.", self.end - self.current);
+    }
+    /// Allocate `count` contiguous frames, which are never recycled, so they
+    /// only come from the frames never used yet
+    pub fn alloc_contiguous(&mut self, count: usize) -> Option<PhysPageNum> {
+        if self.end - self.current < count {
+            self.stats.failures += 1;
+            return None;
+        }
+        let ppn = self.current;
+        self.current += count;
+        self.stats.contiguous += count;
+        self.stats.allocated += count;
+        self.stats.peak = self.stats.peak.max(self.stats.allocated);
+        Some(ppn.into())
+    }
+    /// The byte of the bitmap holding the bit of `ppn`, and the bit in it
+    fn bit(&self, ppn: usize) -> (&'static mut u8, u8) {
+        let index = ppn - self.start;
+        let byte = index / 8;
+        let bytes = PhysPageNum::from(self.bitmap + byte / PAGE_SIZE).get_bytes_array();
+        (&mut bytes[byte % PAGE_SIZE], 1 << (index % 8))
+    }
+    /// Whether `ppn` is in the free list
+    fn is_free(&self, ppn: usize) -> bool {
+        let (byte, mask) = self.bit(ppn);
+        *byte & mask != 0
+    }
+    /// Record whether `ppn` is in the free list
+    fn set_free(&mut self, ppn: usize, free: bool) {
+        let (byte, mask) = self.bit(ppn);
+        if free {
+            *byte |= mask;
+        } else {
+            *byte &= !mask;
+        }
+    }
+}
+impl FrameAllocator for FreeListFrameAllocator {
+    fn new() -> Self {
+        Self {
+            bitmap: 0,
+            start: 0,
+            current: 0,
+            end: 0,
+            free_head: 0,
+            stats: FrameStats::default(),
+        }
+    }
+    fn alloc(&mut self) -> Option<PhysPageNum> {
+        let ppn = if self.free_head != 0 {
+            let ppn = self.free_head;
+            self.free_head = PhysPageNum::from(ppn).get_mut::<FreeFrame>().next;
+            self.set_free(ppn, false);
+            ppn
+        } else if self.current == self.end {
+            self.stats.failures += 1;
+            return None;
+        } else {
+            self.current += 1;
+            self.current - 1
+        };
+        self.stats.allocs += 1;
+        self.stats.allocated += 1;
+        self.stats.peak = self.stats.peak.max(self.stats.allocated);
+        Some(ppn.into())
+    }
+    fn dealloc(&mut self, ppn: PhysPageNum) {
+        let ppn = ppn.0;
+        // validity check
+        if ppn < self.start || ppn >= self.current || self.is_free(ppn) {
+            panic!("Frame ppn={:#x} has not been allocated!", ppn);
+        }
+        // recycle
+        self.set_free(ppn, true);
+        PhysPageNum::from(ppn).get_mut::<FreeFrame>().next = self.free_head;
+        self.free_head = ppn;
+        self.stats.deallocs += 1;
+        self.stats.allocated -= 1;
+    }
+}
+
+type FrameAllocatorImpl = FreeListFrameAllocator;
+
+lazy_static! {
+    /// frame allocator instance through lazy_static!
+    pub static ref FRAME_ALLOCATOR: UPSafeCell<FrameAllocatorImpl> =
+        unsafe { UPSafeCell::new(FrameAllocatorImpl::new()) };
+}
+
+/// initiate the frame allocator using `ekernel` and the end of RAM
+pub fn init_frame_allocator() {
+    extern "C" {
+        fn ekernel();
+    }
+    FRAME_ALLOCATOR.exclusive_access().init(
+        PhysAddr::from(ekernel as usize).ceil(),
+        PhysAddr::from(memory_end()).floor(),
+    );
+}
+
+/// Allocate a physical page frame in FrameTracker style
+pub fn frame_alloc() -> Option<FrameTracker> {
+    FRAME_ALLOCATOR
+        .exclusive_access()
+        .alloc()
+        .map(FrameTracker::new)
+}
+
+/// Allocate `count` contiguous physical page frames for good, without
+/// cleaning them, e.g. to grow the kernel heap
+pub fn frame_alloc_contiguous(count: usize) -> Option<PhysPageNum> {
+    FRAME_ALLOCATOR.exclusive_access().alloc_contiguous(count)
+}
+
+/// Deallocate a physical page frame with a given ppn
+pub fn frame_dealloc(ppn: PhysPageNum) {
+    FRAME_ALLOCATOR.exclusive_access().dealloc(ppn);
+}
+
+/// The usage statistics of the frame allocator
+pub fn frame_stats() -> FrameStats {
+    FRAME_ALLOCATOR.exclusive_access().stats
+}
+
+/// Print the usage statistics of the frame allocator
+pub fn print_frame_stats() {
+    let stats = frame_stats();
+    println!(
+        "[kernel] frames: {} total, {} allocated, {} free, {} peak, {} allocs, {} deallocs, {} contiguous, {} failures",
+        stats.total,
+        stats.allocated,
+        stats.free(),
+        stats.peak,
+        stats.allocs,
+        stats.deallocs,
+        stats.contiguous,
+        stats.failures
+    );
+}
@@ -0,0 +1,681 @@
+//! Implementation of [`MapArea`] and [`MemorySet`].
+
+use super::{frame_alloc, frame_stats, FrameTracker};
+use super::{PTEFlags, PageTable, PageTableEntry};
+use super::{PhysAddr, PhysPageNum, VirtAddr, VirtPageNum};
+use super::{StepByOne, VPNRange};
+use crate::config::{PAGE_SIZE, TRAMPOLINE, TRAP_CONTEXT_BASE, USER_HEAP_SIZE, USER_STACK_SIZE};
+use crate::fdt::{memory_end, mmio_regions};
+use crate::sync::UPSafeCell;
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::arch::asm;
+use lazy_static::*;
+use riscv::register::satp;
+use xmas_elf::{header, program};
+
+extern "C" {
+    fn stext();
+    fn etext();
+    fn srodata();
+    fn erodata();
+    fn sdata();
+    fn edata();
+    fn sbss_with_stack();
+    fn ebss();
+    fn ekernel();
+    fn strampoline();
+}
+
+/// The end of the lower half of the SV39 address space, where user mappings live
+const USER_SPACE_END: usize = 1 << 38;
+
+lazy_static! {
+    /// The kernel's initial memory mapping(kernel address space)
+    pub static ref KERNEL_SPACE: Arc<UPSafeCell<MemorySet>> =
+        Arc::new(unsafe { UPSafeCell::new(MemorySet::new_kernel()) });
+}
+
+/// the kernel token
+pub fn kernel_token() -> usize {
+    KERNEL_SPACE.exclusive_access().token()
+}
+
+/// address space
+pub struct MemorySet {
+    page_table: PageTable,
+    areas: Vec<MapArea>,
+    /// `[bottom, limit)` reserved for the heap of a user space, which only
+    /// the program break maps
+    heap_range: Option<(usize, usize)>,
+}
+
+impl MemorySet {
+    /// Create a new empty `MemorySet`.
+    pub fn new_bare() -> Self {
+        Self {
+            page_table: PageTable::new(),
+            areas: Vec::new(),
+            heap_range: None,
+        }
+    }
+    /// The range `[bottom, limit)` reserved for the heap of a user space
+    pub fn heap_range(&self) -> Option<(usize, usize)> {
+        self.heap_range
+    }
+    /// Get the page table token
+    pub fn token(&self) -> usize {
+        self.page_table.token()
+    }
+    /// Assume that no conflicts.
+    pub fn insert_framed_area(
+        &mut self,
+        start_va: VirtAddr,
+        end_va: VirtAddr,
+        permission: MapPermission,
+    ) {
+        self.push(
+            MapArea::new(start_va, end_va, MapType::Framed, permission),
+            None,
+        );
+    }
+    /// Remove the area which starts at `start_vpn`, unmapping all of its pages.
+    pub fn remove_area_with_start_vpn(&mut self, start_vpn: VirtPageNum) {
+        if let Some((idx, area)) = self
+            .areas
+            .iter_mut()
+            .enumerate()
+            .find(|(_, area)| area.vpn_range.get_start() == start_vpn)
+        {
+            area.unmap(&mut self.page_table);
+            self.areas.remove(idx);
+        }
+    }
+    fn push(&mut self, mut map_area: MapArea, data: Option<&[u8]>) {
+        map_area.map(&mut self.page_table);
+        if let Some(data) = data {
+            map_area.copy_data(&self.page_table, 0, data);
+        }
+        self.areas.push(map_area);
+    }
+    /// Push a framed area whose data starts at `offset` in its first page.
+    fn push_with_offset(&mut self, mut map_area: MapArea, offset: usize, data: &[u8]) {
+        map_area.map(&mut self.page_table);
+        map_area.copy_data(&self.page_table, offset, data);
+        self.areas.push(map_area);
+    }
+    /// Mention that trampoline is not collected by areas.
+    fn map_trampoline(&mut self) {
+        self.page_table.map(
+            VirtAddr::from(TRAMPOLINE).into(),
+            PhysAddr::from(strampoline as usize).into(),
+            PTEFlags::R | PTEFlags::X,
+        );
+    }
+    /// Without kernel stacks.
+    pub fn new_kernel() -> Self {
+        let mut memory_set = Self::new_bare();
+        // map trampoline
+        memory_set.map_trampoline();
+        // map kernel sections
+        info!(".text [{:#x}, {:#x})", stext as usize, etext as usize);
+        info!(".rodata [{:#x}, {:#x})", srodata as usize, erodata as usize);
+        info!(".data [{:#x}, {:#x})", sdata as usize, edata as usize);
+        info!(
+            ".bss [{:#x}, {:#x})",
+            sbss_with_stack as usize, ebss as usize
+        );
+        info!("mapping .text section");
+        memory_set.push(
+            MapArea::new(
+                (stext as usize).into(),
+                (etext as usize).into(),
+                MapType::Identical,
+                MapPermission::R | MapPermission::X,
+            ),
+            None,
+        );
+        info!("mapping .rodata section");
+        memory_set.push(
+            MapArea::new(
+                (srodata as usize).into(),
+                (erodata as usize).into(),
+                MapType::Identical,
+                MapPermission::R,
+            ),
+            None,
+        );
+        info!("mapping .data section");
+        memory_set.push(
+            MapArea::new(
+                (sdata as usize).into(),
+                (edata as usize).into(),
+                MapType::Identical,
+                MapPermission::R | MapPermission::W,
+            ),
+            None,
+        );
+        info!("mapping .bss section");
+        memory_set.push(
+            MapArea::new(
+                (sbss_with_stack as usize).into(),
+                (ebss as usize).into(),
+                MapType::Identical,
+                MapPermission::R | MapPermission::W,
+            ),
+            None,
+        );
+        info!("mapping physical memory");
+        memory_set.push(
+            MapArea::new(
+                (ekernel as usize).into(),
+                memory_end().into(),
+                MapType::Identical,
+                MapPermission::R | MapPermission::W,
+            ),
+            None,
+        );
+        info!("mapping memory-mapped registers");
+        for (start, len) in mmio_regions() {
+            memory_set.push(
+                MapArea::new(
+                    start.into(),
+                    (start + len).into(),
+                    MapType::Identical,
+                    MapPermission::R | MapPermission::W,
+                ),
+                None,
+            );
+        }
+        memory_set
+    }
+    /// Include sections in elf and trampoline and TrapContext and user stack,
+    /// also returns user_sp_base and entry point.
+    ///
+    /// Returns `None` and logs the reason if `elf_data` is not a well-formed
+    /// RV64 executable, so that garbage is never mapped into user space.
+    pub fn from_elf(elf_data: &[u8]) -> Option<(Self, usize, usize)> {
+        let elf = match xmas_elf::ElfFile::new(elf_data) {
+            Ok(elf) => elf,
+            Err(err) => {
+                error!("[kernel] malformed ELF: {}", err);
+                return None;
+            }
+        };
+        let elf_header = elf.header;
+        if elf_header.pt1.magic != [0x7f, 0x45, 0x4c, 0x46] {
+            error!("[kernel] malformed ELF: invalid magic");
+            return None;
+        }
+        if elf_header.pt1.class() != header::Class::SixtyFour
+            || elf_header.pt1.data() != header::Data::LittleEndian
+        {
+            error!("[kernel] malformed ELF: not a little-endian ELF64 file");
+            return None;
+        }
+        if elf_header.pt2.type_().as_type() != header::Type::Executable {
+            error!("[kernel] malformed ELF: not an executable");
+            return None;
+        }
+        let entry_point = elf_header.pt2.entry_point() as usize;
+        let ph_count = elf_header.pt2.ph_count();
+        // check all the loadable segments before mapping any of them
+        let mut segments: Vec<(VirtAddr, VirtAddr, MapPermission, &[u8])> = Vec::new();
+        let mut entry_valid = false;
+        for i in 0..ph_count {
+            let ph = match elf.program_header(i) {
+                Ok(ph) => ph,
+                Err(err) => {
+                    error!("[kernel] malformed ELF: program header {}: {}", i, err);
+                    return None;
+                }
+            };
+            if ph.get_type() != Ok(program::Type::Load) || ph.mem_size() == 0 {
+                continue;
+            }
+            // check the raw values, before `VirtAddr` masks them to 39 bits
+            // and lets them alias the trampoline or the trap context
+            let end = match ph.virtual_addr().checked_add(ph.mem_size()) {
+                Some(end) if end <= USER_SPACE_END as u64 => end as usize,
+                _ => {
+                    error!(
+                        "[kernel] malformed ELF: segment {} [{:#x}, +{:#x}) out of user space",
+                        i,
+                        ph.virtual_addr(),
+                        ph.mem_size()
+                    );
+                    return None;
+                }
+            };
+            let start = ph.virtual_addr() as usize;
+            let mem_size = ph.mem_size() as usize;
+            let file_size = ph.file_size() as usize;
+            let offset = ph.offset() as usize;
+            if file_size > mem_size
+                || offset
+                    .checked_add(file_size)
+                    .map_or(true, |file_end| file_end > elf_data.len())
+            {
+                error!("[kernel] malformed ELF: segment {} exceeds the file", i);
+                return None;
+            }
+            let start_va: VirtAddr = start.into();
+            let end_va: VirtAddr = end.into();
+            if segments.iter().any(|(other_start, other_end, _, _)| {
+                start_va.floor() < other_end.ceil() && other_start.floor() < end_va.ceil()
+            }) {
+                error!("[kernel] malformed ELF: segment {} overlaps another one", i);
+                return None;
+            }
+            let mut map_perm = MapPermission::U;
+            let ph_flags = ph.flags();
+            if ph_flags.is_read() {
+                map_perm |= MapPermission::R;
+            }
+            if ph_flags.is_write() {
+                map_perm |= MapPermission::W;
+            }
+            if ph_flags.is_execute() {
+                map_perm |= MapPermission::X;
+                entry_valid |= (start..end).contains(&entry_point);
+            }
+            segments.push((
+                start_va,
+                end_va,
+                map_perm,
+                &elf_data[offset..offset + file_size],
+            ));
+        }
+        if !entry_valid {
+            error!(
+                "[kernel] malformed ELF: entry {:#x} is not in an executable segment",
+                entry_point
+            );
+            return None;
+        }
+        let mut memory_set = Self::new_bare();
+        // map trampoline
+        memory_set.map_trampoline();
+        // map program segments of elf, with U flag;
+        // the part of a segment beyond its file data (e.g. .bss) stays zeroed
+        let mut max_end_vpn = VirtPageNum(0);
+        for (start_va, end_va, map_perm, data) in segments {
+            let map_area = MapArea::new(start_va, end_va, MapType::Framed, map_perm);
+            max_end_vpn = max_end_vpn.max(map_area.vpn_range.get_end());
+            memory_set.push_with_offset(map_area, start_va.page_offset(), data);
+        }
+        // the heap right after the image, empty until the program break is
+        // moved by sbrk, with room reserved to grow
+        let heap_bottom: usize = VirtAddr::from(max_end_vpn).into();
+        let heap_limit = heap_bottom + USER_HEAP_SIZE;
+        memory_set.push(
+            MapArea::new(
+                heap_bottom.into(),
+                heap_bottom.into(),
+                MapType::Framed,
+                MapPermission::R | MapPermission::W | MapPermission::U,
+            ),
+            None,
+        );
+        memory_set.heap_range = Some((heap_bottom, heap_limit));
+        // map user stack with U flags, above the heap
+        let mut user_stack_bottom = heap_limit;
+        // guard page
+        user_stack_bottom += PAGE_SIZE;
+        let user_stack_top = user_stack_bottom + USER_STACK_SIZE;
+        if user_stack_top > USER_SPACE_END {
+            error!("[kernel] malformed ELF: no room left for the heap and the user stack");
+            return None;
+        }
+        memory_set.push(
+            MapArea::new(
+                user_stack_bottom.into(),
+                user_stack_top.into(),
+                MapType::Framed,
+                MapPermission::R | MapPermission::W | MapPermission::U,
+            ),
+            None,
+        );
+        // map TrapContext
+        memory_set.push(
+            MapArea::new(
+                TRAP_CONTEXT_BASE.into(),
+                TRAMPOLINE.into(),
+                MapType::Framed,
+                MapPermission::R | MapPermission::W,
+            ),
+            None,
+        );
+        Some((memory_set, user_stack_top, entry_point))
+    }
+    /// Create a new address space by copy code&data from a exited process's address space.
+    pub fn from_existed_user(user_space: &Self) -> Self {
+        let mut memory_set = Self::new_bare();
+        memory_set.heap_range = user_space.heap_range;
+        // map trampoline
+        memory_set.map_trampoline();
+        // copy data sections/trap_context/user_stack
+        for area in user_space.areas.iter() {
+            let new_area = MapArea::from_another(area);
+            memory_set.push(new_area, None);
+            // copy data from another space
+            for vpn in area.vpn_range {
+                let src_ppn = user_space.translate(vpn).unwrap().ppn();
+                let dst_ppn = memory_set.translate(vpn).unwrap().ppn();
+                dst_ppn
+                    .get_bytes_array()
+                    .copy_from_slice(src_ppn.get_bytes_array());
+            }
+        }
+        memory_set
+    }
+    /// Change page table by writing satp CSR Register.
+    pub fn activate(&self) {
+        let satp = self.page_table.token();
+        unsafe {
+            satp::write(satp);
+            asm!("sfence.vma");
+        }
+    }
+    /// Translate a virtual page number to a page table entry
+    pub fn translate(&self, vpn: VirtPageNum) -> Option<PageTableEntry> {
+        self.page_table.translate(vpn)
+    }
+    /// Whether `[start, end)` is a non-empty range of user space, starting
+    /// at a page boundary, and apart from the range reserved for the heap.
+    /// It takes raw addresses, as `VirtAddr` masks them to 39 bits.
+    fn is_mmap_range(&self, start: usize, end: usize) -> bool {
+        start % PAGE_SIZE == 0
+            && start < end
+            && end <= USER_SPACE_END
+            && self
+                .heap_range
+                .map_or(true, |(bottom, limit)| end <= bottom || limit <= start)
+    }
+    /// Whether any area other than the `except`-th one has pages in `[start_vpn, end_vpn)`
+    fn overlaps_area(&self, start_vpn: VirtPageNum, end_vpn: VirtPageNum, except: usize) -> bool {
+        self.areas.iter().enumerate().any(|(idx, area)| {
+            idx != except
+                && area.vpn_range.get_start() < area.vpn_range.get_end()
+                && area.vpn_range.get_start() < end_vpn
+                && start_vpn < area.vpn_range.get_end()
+        })
+    }
+    /// Whether there are enough frames left to map `pages` pages
+    fn enough_frames(pages: usize) -> bool {
+        // at worst a page table page every 512 pages
+        pages + pages.div_ceil(512) + 2 <= frame_stats().free()
+    }
+    /// Map `[start, end)`, where `start` must be page aligned, to newly allocated
+    /// zeroed frames with `permission`. Returns `false` without mapping anything
+    /// if the range is not in user space, overlaps the heap range, any page in
+    /// it is mapped already, or there are not enough frames left.
+    pub fn mmap(&mut self, start: usize, end: usize, permission: MapPermission) -> bool {
+        if !self.is_mmap_range(start, end) {
+            return false;
+        }
+        let (start_va, end_va) = (VirtAddr::from(start), VirtAddr::from(end));
+        let vpn_range = VPNRange::new(start_va.floor(), end_va.ceil());
+        if vpn_range
+            .into_iter()
+            .any(|vpn| self.translate(vpn).is_some_and(|pte| pte.is_valid()))
+        {
+            return false;
+        }
+        if !Self::enough_frames(vpn_range.get_end().0 - vpn_range.get_start().0) {
+            return false;
+        }
+        self.insert_framed_area(start_va, end_va, permission);
+        true
+    }
+    /// Unmap `[start, end)`, where `start` must be page aligned, splitting the
+    /// framed areas it cuts through. Returns `false` without unmapping anything
+    /// if the range is not in user space, overlaps the heap range, or any page
+    /// in it is not mapped by a framed area.
+    pub fn munmap(&mut self, start: usize, end: usize) -> bool {
+        if !self.is_mmap_range(start, end) {
+            return false;
+        }
+        let (start_vpn, end_vpn) = (VirtAddr::from(start).floor(), VirtAddr::from(end).ceil());
+        if VPNRange::new(start_vpn, end_vpn).into_iter().any(|vpn| {
+            !self.areas.iter().any(|area| {
+                area.map_type == MapType::Framed
+                    && area.vpn_range.get_start() <= vpn
+                    && vpn < area.vpn_range.get_end()
+            })
+        }) {
+            return false;
+        }
+        let mut idx = 0;
+        while idx < self.areas.len() {
+            let area = &self.areas[idx];
+            if area.vpn_range.get_end() <= start_vpn || end_vpn <= area.vpn_range.get_start() {
+                idx += 1;
+                continue;
+            }
+            // keep the parts out of the range, which the loop then skips
+            let mut area = self.areas.swap_remove(idx);
+            if end_vpn < area.vpn_range.get_end() {
+                self.areas.push(area.split_off(end_vpn));
+            }
+            if start_vpn > area.vpn_range.get_start() {
+                let middle = area.split_off(start_vpn);
+                self.areas.push(area);
+                area = middle;
+            }
+            area.unmap(&mut self.page_table);
+        }
+        true
+    }
+    /// Shrink the area which starts at `start`, so that it ends at `new_end`.
+    /// Returns `false` if there is no such area, or it ends before `new_end`.
+    pub fn shrink_to(&mut self, start: VirtAddr, new_end: VirtAddr) -> bool {
+        let new_end = new_end.ceil();
+        let Some(area) = self.areas.iter_mut().find(|area| {
+            area.vpn_range.get_start() == start.floor()
+                && area.vpn_range.get_start() <= new_end
+                && new_end <= area.vpn_range.get_end()
+        }) else {
+            return false;
+        };
+        area.shrink_to(&mut self.page_table, new_end);
+        true
+    }
+    /// Grow the area which starts at `start`, so that it ends at `new_end`.
+    /// Returns `false` without mapping anything if there is no such area, it
+    /// ends after `new_end`, the new pages are out of user space or overlap
+    /// another area, or there are not enough frames left.
+    pub fn append_to(&mut self, start: VirtAddr, new_end: VirtAddr) -> bool {
+        if !start.aligned() || new_end.0 < start.0 || new_end.0 > USER_SPACE_END {
+            return false;
+        }
+        let Some(idx) = self
+            .areas
+            .iter()
+            .position(|area| area.vpn_range.get_start() == start.floor())
+        else {
+            return false;
+        };
+        let (end, new_end) = (self.areas[idx].vpn_range.get_end(), new_end.ceil());
+        if new_end < end
+            || self.overlaps_area(end, new_end, idx)
+            || !Self::enough_frames(new_end.0 - end.0)
+        {
+            return false;
+        }
+        self.areas[idx].append_to(&mut self.page_table, new_end);
+        true
+    }
+    /// Remove all `MapArea`
+    pub fn recycle_data_pages(&mut self) {
+        self.areas.clear();
+    }
+}
+
+/// map area structure, controls a contiguous piece of virtual memory
+pub struct MapArea {
+    vpn_range: VPNRange,
+    data_frames: BTreeMap<VirtPageNum, FrameTracker>,
+    map_type: MapType,
+    map_perm: MapPermission,
+}
+
+impl MapArea {
+    /// Create a map area covering `[start_va, end_va)`
+    pub fn new(
+        start_va: VirtAddr,
+        end_va: VirtAddr,
+        map_type: MapType,
+        map_perm: MapPermission,
+    ) -> Self {
+        let start_vpn: VirtPageNum = start_va.floor();
+        let end_vpn: VirtPageNum = end_va.ceil();
+        Self {
+            vpn_range: VPNRange::new(start_vpn, end_vpn),
+            data_frames: BTreeMap::new(),
+            map_type,
+            map_perm,
+        }
+    }
+    /// Create a map area with the same range and permission as `another`
+    pub fn from_another(another: &Self) -> Self {
+        Self {
+            vpn_range: VPNRange::new(another.vpn_range.get_start(), another.vpn_range.get_end()),
+            data_frames: BTreeMap::new(),
+            map_type: another.map_type,
+            map_perm: another.map_perm,
+        }
+    }
+    /// Split the area at `at`, keeping `[start, at)` and returning `[at, end)`
+    /// with its frames
+    pub fn split_off(&mut self, at: VirtPageNum) -> Self {
+        let (start, end) = (self.vpn_range.get_start(), self.vpn_range.get_end());
+        assert!(start < at && at < end, "split {:?} out of the area", at);
+        self.vpn_range = VPNRange::new(start, at);
+        Self {
+            vpn_range: VPNRange::new(at, end),
+            data_frames: self.data_frames.split_off(&at),
+            map_type: self.map_type,
+            map_perm: self.map_perm,
+        }
+    }
+    /// Unmap the pages from `new_end` to the end of the area
+    pub fn shrink_to(&mut self, page_table: &mut PageTable, new_end: VirtPageNum) {
+        for vpn in VPNRange::new(new_end, self.vpn_range.get_end()) {
+            self.unmap_one(page_table, vpn);
+        }
+        self.vpn_range = VPNRange::new(self.vpn_range.get_start(), new_end);
+    }
+    /// Map the pages from the end of the area to `new_end`
+    pub fn append_to(&mut self, page_table: &mut PageTable, new_end: VirtPageNum) {
+        for vpn in VPNRange::new(self.vpn_range.get_end(), new_end) {
+            self.map_one(page_table, vpn);
+        }
+        self.vpn_range = VPNRange::new(self.vpn_range.get_start(), new_end);
+    }
+    /// Map a single page of the area
+    pub fn map_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
+        let ppn: PhysPageNum;
+        match self.map_type {
+            MapType::Identical => {
+                ppn = PhysPageNum(vpn.0);
+            }
+            MapType::Framed => {
+                let frame = frame_alloc().unwrap();
+                ppn = frame.ppn;
+                self.data_frames.insert(vpn, frame);
+            }
+        }
+        let pte_flags = PTEFlags::from_bits(self.map_perm.bits).unwrap();
+        page_table.map(vpn, ppn, pte_flags);
+    }
+    /// Unmap a single page of the area
+    pub fn unmap_one(&mut self, page_table: &mut PageTable, vpn: VirtPageNum) {
+        if self.map_type == MapType::Framed {
+            self.data_frames.remove(&vpn);
+        }
+        page_table.unmap(vpn);
+    }
+    /// Map all pages of the area
+    pub fn map(&mut self, page_table: &mut PageTable) {
+        for vpn in self.vpn_range {
+            self.map_one(page_table, vpn);
+        }
+    }
+    /// Unmap all pages of the area
+    pub fn unmap(&mut self, page_table: &mut PageTable) {
+        for vpn in self.vpn_range {
+            self.unmap_one(page_table, vpn);
+        }
+    }
+    /// data: starts at `offset` in the first page but maybe with shorter length
+    /// assume that all frames were cleared before
+    pub fn copy_data(&mut self, page_table: &PageTable, offset: usize, data: &[u8]) {
+        assert_eq!(self.map_type, MapType::Framed);
+        let mut start: usize = 0;
+        let mut page_offset = offset;
+        let mut current_vpn = self.vpn_range.get_start();
+        let len = data.len();
+        while start < len {
+            let src = &data[start..len.min(start + PAGE_SIZE - page_offset)];
+            let dst = &mut page_table
+                .translate(current_vpn)
+                .unwrap()
+                .ppn()
+                .get_bytes_array()[page_offset..page_offset + src.len()];
+            dst.copy_from_slice(src);
+            start += src.len();
+            page_offset = 0;
+            current_vpn.step();
+        }
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+/// map type for memory set: identical or framed
+pub enum MapType {
+    /// virtual page number equals to physical page number
+    Identical,
+    /// each virtual page is backed by a newly allocated frame
+    Framed,
+}
+
+bitflags! {
+    /// map permission corresponding to that in pte: `R W X U`
+    pub struct MapPermission: u8 {
+        ///Readable
+        const R = 1 << 1;
+        ///Writable
+        const W = 1 << 2;
+        ///Excutable
+        const X = 1 << 3;
+        ///Accessible in U mode
+        const U = 1 << 4;
+    }
+}
+
+/// remap test in kernel space
+#[allow(unused)]
+pub fn remap_test() {
+    let mut kernel_space = KERNEL_SPACE.exclusive_access();
+    let mid_text: VirtAddr = ((stext as usize + etext as usize) / 2).into();
+    let mid_rodata: VirtAddr = ((srodata as usize + erodata as usize) / 2).into();
+    let mid_data: VirtAddr = ((sdata as usize + edata as usize) / 2).into();
+    assert!(!kernel_space
+        .page_table
+        .translate(mid_text.floor())
+        .unwrap()
+        .writable(),);
+    assert!(!kernel_space
+        .page_table
+        .translate(mid_rodata.floor())
+        .unwrap()
+        .writable(),);
+    assert!(!kernel_space
+        .page_table
+        .translate(mid_data.floor())
+        .unwrap()
+        .executable(),);
+    println!("remap_test passed!");
+}
@@ -0,0 +1,30 @@
+//! Memory management implementation
+//!
+//! SV39 page-based virtual-memory architecture for RV64 systems, and
+//! everything about memory management, like frame allocator, page table,
+//! map area and memory set, is implemented here.
+//!
+//! Every task or process has a memory_set to control its virtual memory.
+
+mod address;
+mod frame_allocator;
+mod memory_set;
+mod page_table;
+mod user_ptr;
+
+use address::VPNRange;
+pub use address::{PhysAddr, PhysPageNum, StepByOne, VirtAddr, VirtPageNum};
+pub use frame_allocator::{
+    frame_alloc, frame_alloc_contiguous, frame_dealloc, frame_stats, print_frame_stats, FrameStats,
+    FrameTracker,
+};
+pub use memory_set::{kernel_token, remap_test, MapPermission, MemorySet, KERNEL_SPACE};
+use page_table::PTEFlags;
+pub use page_table::{translated_user_buffer, PageTable, PageTableEntry};
+pub use user_ptr::{copy_from_user, copy_str_from_user, copy_to_user, UserPtr, UserSlice};
+
+/// initiate the frame allocator and activate the kernel address space
+pub fn init() {
+    frame_allocator::init_frame_allocator();
+    KERNEL_SPACE.exclusive_access().activate();
+}
@@ -0,0 +1,127 @@
+//! Checked access to the memory of user tasks
+//!
+//! A syscall must never trust a pointer passed by the user: every access goes
+//! through the page table of the task, and fails with `None` unless the whole
+//! range is mapped with the `U` flag and the required permission, so that the
+//! syscall can return `EFAULT` instead of touching kernel memory or panicking.
+use super::translated_user_buffer;
+use crate::config::{PAGE_SIZE, PATH_MAX};
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::marker::PhantomData;
+
+/// Copy `src` to `[dst, dst + src.len())` in the address space `token`,
+/// returns `None` if the destination is not writable by the user
+pub fn copy_to_user(token: usize, dst: *mut u8, src: &[u8]) -> Option<()> {
+    let mut start = 0;
+    for buffer in translated_user_buffer(token, dst, src.len(), true)? {
+        buffer.copy_from_slice(&src[start..start + buffer.len()]);
+        start += buffer.len();
+    }
+    Some(())
+}
+
+/// Copy `[src, src + dst.len())` in the address space `token` to `dst`,
+/// returns `None` if the source is not readable by the user
+pub fn copy_from_user(token: usize, dst: &mut [u8], src: *const u8) -> Option<()> {
+    let mut start = 0;
+    for buffer in translated_user_buffer(token, src, dst.len(), false)? {
+        dst[start..start + buffer.len()].copy_from_slice(buffer);
+        start += buffer.len();
+    }
+    Some(())
+}
+
+/// Copy a string ending with `\0` at `ptr` in the address space `token`,
+/// returns `None` if it is not readable by the user, not valid UTF-8, or
+/// does not end within [`PATH_MAX`] bytes
+pub fn copy_str_from_user(token: usize, ptr: *const u8) -> Option<String> {
+    let mut bytes = Vec::new();
+    let mut va = ptr as usize;
+    loop {
+        // check a page at a time, as the string may end anywhere in it
+        let page_end = (va & !(PAGE_SIZE - 1)).checked_add(PAGE_SIZE)?;
+        let chunk = translated_user_buffer(token, va as *const u8, page_end - va, false)?;
+        for &c in chunk.iter().flat_map(|buffer| buffer.iter()) {
+            if c == 0 {
+                return String::from_utf8(bytes).ok();
+            }
+            // no room left for the ending `\0`
+            if bytes.len() + 1 == PATH_MAX {
+                return None;
+            }
+            bytes.push(c);
+        }
+        va = page_end;
+    }
+}
+
+/// A byte buffer in the address space of a user task
+#[derive(Copy, Clone)]
+pub struct UserSlice {
+    token: usize,
+    ptr: *mut u8,
+    len: usize,
+}
+
+impl UserSlice {
+    /// The buffer `[ptr, ptr + len)` in the address space `token`
+    pub fn new(token: usize, ptr: *mut u8, len: usize) -> Self {
+        Self { token, ptr, len }
+    }
+    /// Whether the user may write to the whole buffer
+    pub fn writable(&self) -> bool {
+        translated_user_buffer(self.token, self.ptr, self.len, true).is_some()
+    }
+    /// The pieces of the buffer in each page, if the user may read the whole
+    /// buffer. The buffer is not copied, and the range is checked before
+    /// anything is allocated, so a huge `len` fails with `None` instead of
+    /// exhausting the kernel heap.
+    pub fn buffers(&self) -> Option<Vec<&'static mut [u8]>> {
+        translated_user_buffer(self.token, self.ptr, self.len, false)
+    }
+    /// Copy `data` to the start of the buffer, which must be long enough
+    pub fn write(&self, data: &[u8]) -> Option<()> {
+        assert!(data.len() <= self.len);
+        copy_to_user(self.token, self.ptr, data)
+    }
+}
+
+/// A value of type `T` in the address space of a user task
+pub struct UserPtr<T> {
+    slice: UserSlice,
+    _marker: PhantomData<T>,
+}
+
+impl<T> UserPtr<T> {
+    /// The value at `ptr` in the address space `token`
+    pub fn new(token: usize, ptr: *mut T) -> Self {
+        Self {
+            slice: UserSlice::new(token, ptr as *mut u8, core::mem::size_of::<T>()),
+            _marker: PhantomData,
+        }
+    }
+    /// Whether the user may write to the value
+    pub fn writable(&self) -> bool {
+        self.slice.writable()
+    }
+    /// Copy the value from the user, `T` must be valid for any bit pattern
+    pub fn read(&self) -> Option<T>
+    where
+        T: Copy,
+    {
+        let mut val = core::mem::MaybeUninit::<T>::uninit();
+        let bytes = unsafe {
+            core::slice::from_raw_parts_mut(val.as_mut_ptr() as *mut u8, core::mem::size_of::<T>())
+        };
+        copy_from_user(self.slice.token, bytes, self.slice.ptr)?;
+        Some(unsafe { val.assume_init() })
+    }
+    /// Copy `val` to the user
+    pub fn write(&self, val: &T) -> Option<()> {
+        let bytes = unsafe {
+            core::slice::from_raw_parts(val as *const T as *const u8, core::mem::size_of::<T>())
+        };
+        self.slice.write(bytes)
+    }
+}
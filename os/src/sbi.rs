@@ -1,14 +1,28 @@
 //! SBI call wrappers
 
+use crate::fdt::virt_test_base;
 use core::arch::asm;
 
 const SBI_SET_TIMER: usize = 0;
 const SBI_CONSOLE_PUTCHAR: usize = 1;
+const SBI_CONSOLE_GETCHAR: usize = 2;
 const SBI_SHUTDOWN: usize = 8;
+/// System Reset Extension
+const SBI_EXT_SRST: usize = 0x5352_5354;
+const SBI_SRST_SYSTEM_RESET: usize = 0;
+const SBI_SRST_TYPE_SHUTDOWN: usize = 0;
+const SBI_SRST_REASON_NONE: usize = 0;
+const SBI_SRST_REASON_SYSTEM_FAILURE: usize = 1;
 
 /// general sbi call
 #[inline(always)]
 fn sbi_call(which: usize, arg0: usize, arg1: usize, arg2: usize) -> usize {
+    sbi_call_fid(which, 0, arg0, arg1, arg2)
+}
+
+/// sbi call to function `fid` of extension `eid`, returns the error code in `a0`
+#[inline(always)]
+fn sbi_call_fid(eid: usize, fid: usize, arg0: usize, arg1: usize, arg2: usize) -> usize {
     let mut ret;
     unsafe {
         asm!(
@@ -16,8 +30,8 @@ fn sbi_call(which: usize, arg0: usize, arg1: usize, arg2: usize) -> usize {
             inlateout("x10") arg0 => ret,
             in("x11") arg1,
             in("x12") arg2,
-            in("x16") 0,
-            in("x17") which,
+            in("x16") fid,
+            in("x17") eid,
         );
     }
     ret
@@ -33,8 +47,73 @@ pub fn console_putchar(c: usize) {
     sbi_call(SBI_CONSOLE_PUTCHAR, c, 0, 0);
 }
 
-/// use sbi call to shutdown the kernel
-pub fn shutdown() -> ! {
+/// use sbi call to getchar from console (qemu uart handler),
+/// returns `None` if no input is available
+pub fn console_getchar() -> Option<u8> {
+    match sbi_call(SBI_CONSOLE_GETCHAR, 0, 0, 0) as isize {
+        -1 => None,
+        c => Some(c as u8),
+    }
+}
+
+/// Why the kernel shuts down
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ShutdownReason {
+    /// All applications completed, QEMU exits with 0
+    Success,
+    /// The initial process exited with a non-zero exit code, QEMU exits with 1
+    AppFailure,
+    /// The kernel panicked, QEMU exits with 2
+    KernelPanic,
+}
+
+impl ShutdownReason {
+    /// The exit code of QEMU
+    fn exit_code(self) -> u32 {
+        match self {
+            ShutdownReason::Success => 0,
+            ShutdownReason::AppFailure => 1,
+            ShutdownReason::KernelPanic => 2,
+        }
+    }
+}
+
+/// Power off through the `sifive_test` device of the QEMU virt machine
+fn virt_test_shutdown(reason: ShutdownReason) {
+    const FINISHER_PASS: u32 = 0x5555;
+    const FINISHER_FAIL: u32 = 0x3333;
+    let value = match reason {
+        ShutdownReason::Success => FINISHER_PASS,
+        _ => (reason.exit_code() << 16) | FINISHER_FAIL,
+    };
+    // the device tree may describe a machine without it
+    if let Some(base) = virt_test_base() {
+        unsafe {
+            (base as *mut u32).write_volatile(value);
+        }
+    }
+}
+
+/// Shut down and tell the host why, so that automated test runs can check the exit code.
+///
+/// Tries the QEMU `sifive_test` device, then SBI SRST with a failure reason,
+/// and falls back to the legacy SBI shutdown, which reports no status.
+pub fn shutdown(reason: ShutdownReason) -> ! {
+    virt_test_shutdown(reason);
+    let srst_reason = match reason {
+        ShutdownReason::Success => SBI_SRST_REASON_NONE,
+        _ => SBI_SRST_REASON_SYSTEM_FAILURE,
+    };
+    sbi_call_fid(
+        SBI_EXT_SRST,
+        SBI_SRST_SYSTEM_RESET,
+        SBI_SRST_TYPE_SHUTDOWN,
+        srst_reason,
+        0,
+    );
     sbi_call(SBI_SHUTDOWN, 0, 0, 0);
-    panic!("It should shutdown!");
+    // the panic handler calls shutdown, do not panic again
+    loop {
+        unsafe { asm!("wfi") };
+    }
 }
@@ -0,0 +1,14 @@
+//! Error numbers returned by syscalls
+//!
+//! As in Linux, a failed syscall returns the negated error number.
+
+/// No such process
+pub const ESRCH: isize = -3;
+/// Bad file number
+pub const EBADF: isize = -9;
+/// Bad address
+pub const EFAULT: isize = -14;
+/// Invalid argument
+pub const EINVAL: isize = -22;
+/// Function not implemented
+pub const ENOSYS: isize = -38;
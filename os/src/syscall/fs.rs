@@ -1,19 +1,58 @@
 //! File and filesystem-related syscalls
+use super::{EBADF, EFAULT};
+use crate::console::{poll_stdin, read_stdin, write_bytes};
+use crate::mm::UserSlice;
+use crate::task::{block_current_and_run_next, current_task, current_user_token};
 
+const FD_STDIN: usize = 0;
 const FD_STDOUT: usize = 1;
+const FD_STDERR: usize = 2;
+
+/// read at most `len` bytes from a file with `fd` into `buf`,
+/// blocking until at least one byte is available
+pub fn sys_read(fd: usize, buf: *mut u8, len: usize) -> isize {
+    trace!("kernel: sys_read");
+    if fd != FD_STDIN {
+        return EBADF;
+    }
+    if len == 0 {
+        return 0;
+    }
+    let buf = UserSlice::new(current_user_token(), buf, len);
+    // fail before consuming any input
+    if !buf.writable() {
+        return EFAULT;
+    }
+    poll_stdin();
+    let input = loop {
+        match read_stdin(len, &current_task().unwrap()) {
+            Some(input) => break input,
+            None => block_current_and_run_next(),
+        }
+    };
+    match buf.write(&input) {
+        Some(()) => input.len() as isize,
+        None => EFAULT,
+    }
+}
 
 /// write buf of length `len`  to a file with `fd`
+///
+/// Only stdout and stderr are supported, both go to the console as raw bytes.
 pub fn sys_write(fd: usize, buf: *const u8, len: usize) -> isize {
     trace!("kernel: sys_write");
     match fd {
-        FD_STDOUT => {
-            let slice = unsafe { core::slice::from_raw_parts(buf, len) };
-            let str = core::str::from_utf8(slice).unwrap();
-            print!("{}", str);
+        FD_STDOUT | FD_STDERR => {
+            // the whole buffer is checked before anything is written
+            let Some(buffers) = UserSlice::new(current_user_token(), buf as *mut u8, len).buffers()
+            else {
+                return EFAULT;
+            };
+            for buffer in buffers {
+                write_bytes(buffer);
+            }
             len as isize
         }
-        _ => {
-            panic!("Unsupported fd in sys_write!");
-        }
+        _ => EBADF,
     }
 }
@@ -10,41 +10,222 @@
 //! `sys_` then the name of the syscall. You can find functions like this in
 //! submodules, and you should also implement syscalls this way.
 
+/// read syscall
+const SYSCALL_READ: usize = 63;
 /// write syscall
 const SYSCALL_WRITE: usize = 64;
 /// exit syscall
 const SYSCALL_EXIT: usize = 93;
+/// nanosleep syscall
+const SYSCALL_NANOSLEEP: usize = 101;
 /// yield syscall
 const SYSCALL_YIELD: usize = 124;
+/// setpriority syscall
+const SYSCALL_SET_PRIORITY: usize = 140;
 /// gettime syscall
 const SYSCALL_GET_TIME: usize = 169;
-/// taskinfo syscall
-const SYSCALL_TASK_INFO: usize = 410;
+/// getpid syscall
+const SYSCALL_GETPID: usize = 172;
+/// sbrk syscall
+const SYSCALL_SBRK: usize = 214;
+/// munmap syscall
+const SYSCALL_MUNMAP: usize = 215;
+/// fork syscall
+const SYSCALL_FORK: usize = 220;
+/// exec syscall
+const SYSCALL_EXEC: usize = 221;
+/// mmap syscall
+const SYSCALL_MMAP: usize = 222;
+/// waitpid syscall
+const SYSCALL_WAITPID: usize = 260;
+/// original taskinfo syscall, describing the caller only
+const SYSCALL_TASK_INFO_LEGACY: usize = 410;
+/// taskinfo syscall, describing any task in a versioned layout
+const SYSCALL_TASK_INFO: usize = 411;
+/// syscall statistics syscall
+const SYSCALL_SYSCALL_STATS: usize = 412;
 
+mod errno;
 mod fs;
 mod process;
+mod stats;
 
+pub use errno::*;
 use fs::*;
 use process::*;
+pub use stats::{print_syscall_stats, SyscallStat, SyscallStats, SYSCALL_STATS};
 
-use crate::task::change_syscall_time;
-/// handle syscall exception with `syscall_id` and other arguments
-pub fn syscall(syscall_id: usize, args: [usize; 3]) -> isize {
-    match syscall_id {
-        SYSCALL_WRITE | SYSCALL_EXIT | SYSCALL_YIELD | SYSCALL_GET_TIME | SYSCALL_TASK_INFO =>
-        {
-            //if syscall_id == SYSCALL_WRITE || syscall_id == SYSCALL_TASK_INFO {println!("in test syscall id is {}", syscall_id);}
-            change_syscall_time(syscall_id)
-        },
-        _ => panic!("Unsupported syscall_id: {}", syscall_id),
-    }
+use crate::task::{current_task, record_syscall_enter, record_syscall_exit};
+use crate::timer::get_time_us;
+
+/// The maximum number of syscall arguments, passed in `a0`-`a5`
+pub const MAX_SYSCALL_ARGS: usize = 6;
+
+/// A syscall handler, which decodes its arguments from the raw registers
+type SyscallHandler = fn([usize; MAX_SYSCALL_ARGS]) -> isize;
+
+/// An entry of [`SYSCALL_TABLE`]
+struct SyscallEntry {
+    /// syscall id passed in `a7`
+    id: usize,
+    /// name of the syscall, for tracing
+    name: &'static str,
+    /// number of arguments the syscall takes, at most [`MAX_SYSCALL_ARGS`]
+    arity: usize,
+    /// the handler
+    handler: SyscallHandler,
+}
 
-    match syscall_id {
-        SYSCALL_WRITE => sys_write(args[0], args[1] as *const u8, args[2]),
-        SYSCALL_EXIT => sys_exit(args[0] as i32),
-        SYSCALL_YIELD => sys_yield(),
-        SYSCALL_GET_TIME => sys_get_time(args[0] as *mut TimeVal, args[1]),
-        SYSCALL_TASK_INFO => sys_task_info(args[0] as *mut TaskInfo),
-        _ => panic!("Unsupported syscall_id: {}", syscall_id),
+/// All the supported syscalls.
+///
+/// To add a syscall, register its id, name, arity and handler here.
+const SYSCALL_TABLE: &[SyscallEntry] = &[
+    SyscallEntry {
+        id: SYSCALL_READ,
+        name: "read",
+        arity: 3,
+        handler: |args| sys_read(args[0], args[1] as *mut u8, args[2]),
+    },
+    SyscallEntry {
+        id: SYSCALL_WRITE,
+        name: "write",
+        arity: 3,
+        handler: |args| sys_write(args[0], args[1] as *const u8, args[2]),
+    },
+    SyscallEntry {
+        id: SYSCALL_EXIT,
+        name: "exit",
+        arity: 1,
+        handler: |args| sys_exit(args[0] as i32),
+    },
+    SyscallEntry {
+        id: SYSCALL_NANOSLEEP,
+        name: "nanosleep",
+        arity: 2,
+        handler: |args| sys_nanosleep(args[0] as *const TimeSpec, args[1] as *mut TimeSpec),
+    },
+    SyscallEntry {
+        id: SYSCALL_YIELD,
+        name: "yield",
+        arity: 0,
+        handler: |_| sys_yield(),
+    },
+    SyscallEntry {
+        id: SYSCALL_SET_PRIORITY,
+        name: "set_priority",
+        arity: 1,
+        handler: |args| sys_set_priority(args[0] as isize),
+    },
+    SyscallEntry {
+        id: SYSCALL_GET_TIME,
+        name: "get_time",
+        arity: 2,
+        handler: |args| sys_get_time(args[0] as *mut TimeVal, args[1]),
+    },
+    SyscallEntry {
+        id: SYSCALL_GETPID,
+        name: "getpid",
+        arity: 0,
+        handler: |_| sys_getpid(),
+    },
+    SyscallEntry {
+        id: SYSCALL_SBRK,
+        name: "sbrk",
+        arity: 1,
+        handler: |args| sys_sbrk(args[0] as isize),
+    },
+    SyscallEntry {
+        id: SYSCALL_MUNMAP,
+        name: "munmap",
+        arity: 2,
+        handler: |args| sys_munmap(args[0], args[1]),
+    },
+    SyscallEntry {
+        id: SYSCALL_FORK,
+        name: "fork",
+        arity: 0,
+        handler: |_| sys_fork(),
+    },
+    SyscallEntry {
+        id: SYSCALL_EXEC,
+        name: "exec",
+        arity: 1,
+        handler: |args| sys_exec(args[0] as *const u8),
+    },
+    SyscallEntry {
+        id: SYSCALL_MMAP,
+        name: "mmap",
+        arity: 3,
+        handler: |args| sys_mmap(args[0], args[1], args[2]),
+    },
+    SyscallEntry {
+        id: SYSCALL_WAITPID,
+        name: "waitpid",
+        arity: 2,
+        handler: |args| sys_waitpid(args[0] as isize, args[1] as *mut i32),
+    },
+    SyscallEntry {
+        id: SYSCALL_TASK_INFO_LEGACY,
+        name: "task_info_legacy",
+        arity: 1,
+        handler: |args| sys_task_info_legacy(args[0] as *mut TaskInfoLegacy),
+    },
+    SyscallEntry {
+        id: SYSCALL_TASK_INFO,
+        name: "task_info",
+        arity: 3,
+        handler: |args| sys_task_info(args[0] as isize, args[1] as *mut u8, args[2]),
+    },
+    SyscallEntry {
+        id: SYSCALL_SYSCALL_STATS,
+        name: "syscall_stats",
+        arity: 3,
+        handler: |args| sys_syscall_stats(args[0] as isize, args[1] as *mut SyscallStat, args[2]),
+    },
+];
+
+// every arity must fit in the registers, checked at compile time
+const _: () = {
+    let mut i = 0;
+    while i < SYSCALL_TABLE.len() {
+        assert!(SYSCALL_TABLE[i].arity <= MAX_SYSCALL_ARGS);
+        i += 1;
     }
+};
+
+/// The name of the syscall `syscall_id`, if it is supported
+pub fn syscall_name(syscall_id: usize) -> Option<&'static str> {
+    SYSCALL_TABLE
+        .iter()
+        .find(|entry| entry.id == syscall_id)
+        .map(|entry| entry.name)
+}
+
+/// handle syscall exception with `syscall_id` and other arguments
+///
+/// Returns [`ENOSYS`] instead of bringing the kernel down if `syscall_id` is unknown.
+pub fn syscall(syscall_id: usize, args: [usize; MAX_SYSCALL_ARGS]) -> isize {
+    let pid = current_task().unwrap().getpid();
+    let Some(entry) = SYSCALL_TABLE.iter().find(|entry| entry.id == syscall_id) else {
+        warn!(
+            "[kernel] pid[{}] unsupported syscall_id: {}",
+            pid, syscall_id
+        );
+        return ENOSYS;
+    };
+    trace!(
+        "[kernel] pid[{}] sys_{}{:x?}",
+        pid,
+        entry.name,
+        &args[..entry.arity]
+    );
+    record_syscall_enter(syscall_id);
+    SYSCALL_STATS.exclusive_access().enter(syscall_id);
+    let start = get_time_us();
+    let ret = (entry.handler)(args);
+    // the latency includes the time blocked or switched away
+    let latency = get_time_us() - start;
+    record_syscall_exit(syscall_id, latency);
+    SYSCALL_STATS.exclusive_access().exit(syscall_id, latency);
+    ret
 }
@@ -1,6 +1,17 @@
 //! Process management syscalls
+use alloc::sync::Arc;
+
 use crate::{
-    config::MAX_SYSCALL_NUM, task::{exit_current_and_run_next, get_running_task_syscall, get_running_task_time, suspend_current_and_run_next, TaskStatus}, timer::{get_time_ms, get_time_us}
+    config::MAX_SYSCALL_NUM,
+    loader::get_app_data_by_name,
+    mm::{copy_str_from_user, copy_to_user, MapPermission, UserPtr},
+    syscall::{SyscallStat, EFAULT, EINVAL, ESRCH},
+    task::{
+        add_task, block_current_and_run_next, current_task, current_user_token,
+        exit_current_and_run_next, pid2task, remove_from_pid2task, yield_current_and_run_next,
+        TaskControlBlock, TaskStatus,
+    },
+    timer::{add_timer, duration_to_ticks, get_time, get_time_us},
 };
 
 #[repr(C)]
@@ -10,28 +21,301 @@ pub struct TimeVal {
     pub usec: usize,
 }
 
-/// Task information
+/// A duration for `sys_nanosleep`
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct TimeSpec {
+    /// seconds
+    pub sec: usize,
+    /// nanoseconds, less than 1_000_000_000
+    pub nsec: usize,
+}
+
+/// Task information of the original `sys_task_info(ti)`,
+/// laid out as the user library defines it
+#[repr(C)]
 #[allow(dead_code)]
-pub struct TaskInfo {
-    /// Task status in it's life cycle
-    status: TaskStatus,
+pub struct TaskInfoLegacy {
+    /// Task status in it's life cycle, as the user library numbers it
+    status: u32,
     /// The numbers of syscall called by task
     syscall_times: [u32; MAX_SYSCALL_NUM],
     /// Total running time of task
     time: usize,
 }
 
+/// [`TaskInfoLegacy::status`] of a running task, the user library still
+/// numbers its statuses `UnInit, Ready, Running, Exited`
+const LEGACY_TASK_STATUS_RUNNING: u32 = 2;
+
+/// Version of the [`TaskInfo`] layout, bumped whenever it changes
+pub const TASK_INFO_VERSION: u32 = 2;
+
+/// [`TaskInfo::status`] of a task ready to run
+pub const TASK_STATUS_READY: u32 = 0;
+/// [`TaskInfo::status`] of a running task
+pub const TASK_STATUS_RUNNING: u32 = 1;
+/// [`TaskInfo::status`] of a task waiting for an event
+pub const TASK_STATUS_BLOCKED: u32 = 2;
+/// [`TaskInfo::status`] of an exited task not reaped by its parent yet
+pub const TASK_STATUS_ZOMBIE: u32 = 3;
+
+/// Task information, in a stable layout which C tooling can consume.
+///
+/// Tooling must check `version` before interpreting the rest. Asking for
+/// fewer bytes than the kernel's `size` just drops the fields at the end.
+#[repr(C)]
+pub struct TaskInfo {
+    /// [`TASK_INFO_VERSION`]
+    version: u32,
+    /// Size of the whole structure in the kernel
+    size: u32,
+    /// Process identifier
+    pid: u64,
+    /// One of the `TASK_STATUS_*` codes
+    status: u32,
+    /// Always 0
+    reserved: u32,
+    /// Total running time of task, in milliseconds since it first ran
+    time: u64,
+    /// Time spent in user mode, in microseconds
+    user_time: u64,
+    /// Time spent in the kernel, in microseconds
+    kernel_time: u64,
+    /// Times the task yielded or blocked
+    voluntary_switches: u64,
+    /// Times the task was preempted
+    involuntary_switches: u64,
+    /// Timer ticks which interrupted the task
+    ticks: u64,
+    /// Total times of all the syscalls called, see `sys_syscall_stats` for each one
+    syscall_count: u64,
+    /// Total time spent in syscalls, in microseconds
+    syscall_time: u64,
+}
+
+impl TaskInfo {
+    /// Describe `task`
+    fn new(task: &TaskControlBlock) -> Self {
+        let inner = task.inner_exclusive_access();
+        let stats = &inner.stats;
+        Self {
+            version: TASK_INFO_VERSION,
+            size: core::mem::size_of::<Self>() as u32,
+            pid: task.getpid() as u64,
+            status: match inner.task_status {
+                TaskStatus::Ready => TASK_STATUS_READY,
+                TaskStatus::Running => TASK_STATUS_RUNNING,
+                TaskStatus::Blocked => TASK_STATUS_BLOCKED,
+                TaskStatus::Zombie => TASK_STATUS_ZOMBIE,
+            },
+            reserved: 0,
+            time: (stats.elapsed(get_time_us()) / 1000) as u64,
+            user_time: stats.user_time as u64,
+            kernel_time: stats.kernel_time as u64,
+            voluntary_switches: stats.voluntary_switches as u64,
+            involuntary_switches: stats.involuntary_switches as u64,
+            ticks: stats.ticks as u64,
+            syscall_count: inner.syscall_stats.total_count(),
+            syscall_time: inner.syscall_stats.total_time(),
+        }
+    }
+}
+
 /// task exits and submit an exit code
 pub fn sys_exit(exit_code: i32) -> ! {
-    trace!("[kernel] Application exited with code {}", exit_code);
-    exit_current_and_run_next();
+    trace!("kernel:pid[{}] sys_exit", current_task().unwrap().pid.0);
+    exit_current_and_run_next(exit_code);
     panic!("Unreachable in sys_exit!");
 }
 
 /// current task gives up resources for other tasks
 pub fn sys_yield() -> isize {
-    trace!("kernel: sys_yield");
-    suspend_current_and_run_next();
+    trace!("kernel:pid[{}] sys_yield", current_task().unwrap().pid.0);
+    yield_current_and_run_next();
+    0
+}
+
+/// set the priority of current task, which must be at least 2
+pub fn sys_set_priority(prio: isize) -> isize {
+    trace!(
+        "kernel:pid[{}] sys_set_priority",
+        current_task().unwrap().pid.0
+    );
+    if prio < 2 {
+        return -1;
+    }
+    current_task()
+        .unwrap()
+        .inner_exclusive_access()
+        .set_priority(prio as usize);
+    prio
+}
+
+/// map `len` bytes from `start`, which must be page aligned, to zeroed memory
+/// with `prot` (bit 0: read, bit 1: write, bit 2: exec), at least one of them.
+/// Returns 0, or -1 on bad arguments, if the range overlaps the room reserved
+/// for the heap, or if any page is mapped already.
+pub fn sys_mmap(start: usize, len: usize, prot: usize) -> isize {
+    trace!("kernel:pid[{}] sys_mmap", current_task().unwrap().pid.0);
+    if prot & !0x7 != 0 || prot & 0x7 == 0 {
+        return -1;
+    }
+    let mut permission = MapPermission::from_bits((prot as u8) << 1).unwrap() | MapPermission::U;
+    // write-only pages are reserved in RISC-V
+    if permission.contains(MapPermission::W) {
+        permission |= MapPermission::R;
+    }
+    let Some(end) = start.checked_add(len) else {
+        return -1;
+    };
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    if inner.memory_set.mmap(start, end, permission) {
+        0
+    } else {
+        -1
+    }
+}
+
+/// unmap `len` bytes from `start`, which must be page aligned.
+/// Returns 0, or -1 on bad arguments, if the range overlaps the room reserved
+/// for the heap, or if any page is not mapped.
+pub fn sys_munmap(start: usize, len: usize) -> isize {
+    trace!("kernel:pid[{}] sys_munmap", current_task().unwrap().pid.0);
+    let Some(end) = start.checked_add(len) else {
+        return -1;
+    };
+    let task = current_task().unwrap();
+    let mut inner = task.inner_exclusive_access();
+    if inner.memory_set.munmap(start, end) {
+        0
+    } else {
+        -1
+    }
+}
+
+/// move the program break by `size` bytes.
+/// Returns the old program break, or -1 if it can not be moved.
+pub fn sys_sbrk(size: isize) -> isize {
+    trace!("kernel:pid[{}] sys_sbrk", current_task().unwrap().pid.0);
+    match current_task()
+        .unwrap()
+        .inner_exclusive_access()
+        .change_program_brk(size)
+    {
+        Some(old_brk) => old_brk as isize,
+        None => -1,
+    }
+}
+
+/// get the pid of current task
+pub fn sys_getpid() -> isize {
+    trace!("kernel: sys_getpid pid:{}", current_task().unwrap().pid.0);
+    current_task().unwrap().pid.0 as isize
+}
+
+/// create a child process as a copy of the current one
+pub fn sys_fork() -> isize {
+    trace!("kernel:pid[{}] sys_fork", current_task().unwrap().pid.0);
+    let current_task = current_task().unwrap();
+    let new_task = current_task.fork();
+    let new_pid = new_task.pid.0;
+    // modify trap context of new_task, because it returns immediately after switching
+    let trap_cx = new_task.inner_exclusive_access().get_trap_cx();
+    // we do not have to move to next instruction since we have done it before
+    // for child process, fork returns 0
+    trap_cx.x[10] = 0;
+    // add new task to scheduler
+    add_task(new_task);
+    new_pid as isize
+}
+
+/// replace the program of current task with the app named `path`
+pub fn sys_exec(path: *const u8) -> isize {
+    trace!("kernel:pid[{}] sys_exec", current_task().unwrap().pid.0);
+    let Some(path) = copy_str_from_user(current_user_token(), path) else {
+        return EFAULT;
+    };
+    if let Some(data) = get_app_data_by_name(path.as_str()) {
+        let task = current_task().unwrap();
+        if task.exec(data) {
+            0
+        } else {
+            -1
+        }
+    } else {
+        -1
+    }
+}
+
+/// If there is not a child process whose pid is same as given, return -1.
+/// Else if there is a child process but it is still running, return -2.
+pub fn sys_waitpid(pid: isize, exit_code_ptr: *mut i32) -> isize {
+    trace!(
+        "kernel::pid[{}] sys_waitpid [{}]",
+        current_task().unwrap().pid.0,
+        pid
+    );
+    let task = current_task().unwrap();
+    let exit_code_ptr = UserPtr::new(current_user_token(), exit_code_ptr);
+    // find a child process
+
+    // ---- access current PCB exclusively
+    let mut inner = task.inner_exclusive_access();
+    if !inner
+        .children
+        .iter()
+        .any(|p| pid == -1 || pid as usize == p.getpid())
+    {
+        return -1;
+        // ---- release current PCB
+    }
+    let pair = inner.children.iter().enumerate().find(|(_, p)| {
+        // ++++ temporarily access child PCB exclusively
+        p.inner_exclusive_access().is_zombie() && (pid == -1 || pid as usize == p.getpid())
+        // ++++ release child PCB
+    });
+    if let Some((idx, _)) = pair {
+        // do not reap the child if its exit code can not be delivered
+        if !exit_code_ptr.writable() {
+            return EFAULT;
+        }
+        let child = inner.children.remove(idx);
+        remove_from_pid2task(child.getpid());
+        // confirm that child will be deallocated after being removed from children list
+        assert_eq!(Arc::strong_count(&child), 1);
+        let found_pid = child.getpid();
+        // ++++ temporarily access child PCB exclusively
+        let exit_code = child.inner_exclusive_access().exit_code;
+        // ++++ release child PCB
+        exit_code_ptr.write(&exit_code).unwrap();
+        found_pid as isize
+    } else {
+        -2
+    }
+    // ---- release current PCB automatically
+}
+
+/// block the current task for the duration at `req`, which is never interrupted,
+/// so `rem` is not written
+pub fn sys_nanosleep(req: *const TimeSpec, _rem: *mut TimeSpec) -> isize {
+    trace!(
+        "kernel:pid[{}] sys_nanosleep",
+        current_task().unwrap().pid.0
+    );
+    let Some(req) = UserPtr::new(current_user_token(), req as *mut TimeSpec).read() else {
+        return EFAULT;
+    };
+    if req.nsec >= 1_000_000_000 {
+        return EINVAL;
+    }
+    let ticks = duration_to_ticks(req.sec, req.nsec);
+    if ticks == 0 {
+        return 0;
+    }
+    add_timer(get_time().saturating_add(ticks), current_task().unwrap());
+    block_current_and_run_next();
     0
 }
 
@@ -39,30 +323,86 @@ pub fn sys_yield() -> isize {
 pub fn sys_get_time(ts: *mut TimeVal, _tz: usize) -> isize {
     trace!("kernel: sys_get_time");
     let us = get_time_us();
-    unsafe {
-        *ts = TimeVal {
-            sec: us / 1_000_000,
-            usec: us % 1_000_000,
-        };
+    let time_val = TimeVal {
+        sec: us / 1_000_000,
+        usec: us % 1_000_000,
+    };
+    match UserPtr::new(current_user_token(), ts).write(&time_val) {
+        Some(()) => 0,
+        None => EFAULT,
+    }
+}
+
+/// The original `sys_task_info(ti)`, describing the caller only
+pub fn sys_task_info_legacy(ti: *mut TaskInfoLegacy) -> isize {
+    trace!("kernel: sys_task_info_legacy");
+    let task = current_task().unwrap();
+    let inner = task.inner_exclusive_access();
+    let mut syscall_times = [0; MAX_SYSCALL_NUM];
+    for stat in inner.syscall_stats.as_slice() {
+        syscall_times[stat.id as usize] = stat.count as u32;
+    }
+    let task_info = TaskInfoLegacy {
+        status: LEGACY_TASK_STATUS_RUNNING,
+        syscall_times,
+        time: inner.stats.elapsed(get_time_us()) / 1000,
+    };
+    drop(inner);
+    match UserPtr::new(current_user_token(), ti).write(&task_info) {
+        Some(()) => 0,
+        None => EFAULT,
     }
-    0
 }
 
+/// The task `pid`, or the caller if `pid` is negative
+fn task_by_pid(pid: isize) -> Option<Arc<TaskControlBlock>> {
+    if pid < 0 {
+        current_task()
+    } else {
+        pid2task(pid as usize)
+    }
+}
 
-//use crate::task::TASK_MANAGER;
-/// YOUR JOB: Finish sys_task_info to pass testcases
-pub fn sys_task_info(_ti: *mut TaskInfo) -> isize {
-    //trace!("kernel: sys_task_info");
-    //-1
-    //println!("The number of app is {}",get_num_app());
-    let time_bef = get_running_task_time();
-    
-    //let t1 = get_time_ms() as usize;
-    //println!("time bef is {}, time now is {}", time_bef,t1);
-    unsafe {
-        (*_ti).status = TaskStatus::Running;
-        (*_ti).time = get_time_ms() as usize - time_bef;
-        (*_ti).syscall_times = get_running_task_syscall();
+/// Copy at most `size` bytes of the [`TaskInfo`] of the task `pid`, or of the
+/// caller if `pid` is negative, to `info`. Returns the number of bytes copied.
+pub fn sys_task_info(pid: isize, info: *mut u8, size: usize) -> isize {
+    trace!("kernel: sys_task_info [{}]", pid);
+    let Some(task) = task_by_pid(pid) else {
+        return ESRCH;
+    };
+    let task_info = TaskInfo::new(&task);
+    let bytes = unsafe {
+        core::slice::from_raw_parts(
+            &task_info as *const TaskInfo as *const u8,
+            core::mem::size_of::<TaskInfo>(),
+        )
+    };
+    let len = size.min(bytes.len());
+    match copy_to_user(current_user_token(), info, &bytes[..len]) {
+        Some(()) => len as isize,
+        None => EFAULT,
+    }
+}
+
+/// Copy the statistics of at most `len` syscalls called by the task `pid`, or
+/// by the caller if `pid` is negative, to `stats`, sorted by syscall id.
+/// Returns the number of syscalls the task has called, which may exceed `len`.
+pub fn sys_syscall_stats(pid: isize, stats: *mut SyscallStat, len: usize) -> isize {
+    trace!("kernel: sys_syscall_stats [{}]", pid);
+    let Some(task) = task_by_pid(pid) else {
+        return ESRCH;
+    };
+    let syscall_stats = task.inner_exclusive_access().syscall_stats.clone();
+    let syscall_stats = syscall_stats.as_slice();
+    let n = len.min(syscall_stats.len());
+    let bytes = unsafe {
+        core::slice::from_raw_parts(
+            syscall_stats.as_ptr() as *const u8,
+            n * core::mem::size_of::<SyscallStat>(),
+        )
+    };
+    match copy_to_user(current_user_token(), stats as *mut u8, bytes) {
+        Some(()) => syscall_stats.len() as isize,
+        None => EFAULT,
     }
-    0
 }
@@ -0,0 +1,104 @@
+//! Syscall counting and latency statistics
+//!
+//! Each task keeps a [`SyscallStats`] with an entry for every syscall it has
+//! called, and the kernel keeps one more for all the tasks, dumped at shutdown.
+use super::syscall_name;
+use crate::sync::UPSafeCell;
+use alloc::vec::Vec;
+use lazy_static::*;
+
+/// Statistics of one syscall, in a stable layout copied to the user
+#[repr(C)]
+#[derive(Copy, Clone, Debug)]
+pub struct SyscallStat {
+    /// syscall id
+    pub id: u64,
+    /// Times the syscall was called
+    pub count: u64,
+    /// Total time spent in the syscall, in microseconds
+    pub total_time: u64,
+    /// Longest time spent in one call, in microseconds
+    pub max_time: u64,
+}
+
+/// Statistics of the syscalls called, sorted by syscall id
+#[derive(Clone, Default)]
+pub struct SyscallStats {
+    stats: Vec<SyscallStat>,
+}
+
+impl SyscallStats {
+    /// Create an empty SyscallStats
+    pub fn new() -> Self {
+        Self { stats: Vec::new() }
+    }
+    /// Get the entry of `id`, creating it if it is the first call
+    fn entry(&mut self, id: usize) -> &mut SyscallStat {
+        let index = match self
+            .stats
+            .binary_search_by_key(&(id as u64), |stat| stat.id)
+        {
+            Ok(index) => index,
+            Err(index) => {
+                let stat = SyscallStat {
+                    id: id as u64,
+                    count: 0,
+                    total_time: 0,
+                    max_time: 0,
+                };
+                self.stats.insert(index, stat);
+                index
+            }
+        };
+        &mut self.stats[index]
+    }
+    /// Count a call of `id`, on entering it
+    pub fn enter(&mut self, id: usize) {
+        self.entry(id).count += 1;
+    }
+    /// Account `latency` microseconds to a call of `id`, on returning from it
+    pub fn exit(&mut self, id: usize, latency: usize) {
+        let stat = self.entry(id);
+        stat.total_time += latency as u64;
+        stat.max_time = stat.max_time.max(latency as u64);
+    }
+    /// Times `id` was called
+    pub fn count(&self, id: usize) -> u64 {
+        self.stats
+            .binary_search_by_key(&(id as u64), |stat| stat.id)
+            .map_or(0, |index| self.stats[index].count)
+    }
+    /// Total times of all the syscalls
+    pub fn total_count(&self) -> u64 {
+        self.stats.iter().map(|stat| stat.count).sum()
+    }
+    /// Total time spent in all the syscalls, in microseconds
+    pub fn total_time(&self) -> u64 {
+        self.stats.iter().map(|stat| stat.total_time).sum()
+    }
+    /// The entries, sorted by syscall id
+    pub fn as_slice(&self) -> &[SyscallStat] {
+        &self.stats
+    }
+}
+
+lazy_static! {
+    /// Syscall statistics of all the tasks
+    pub static ref SYSCALL_STATS: UPSafeCell<SyscallStats> =
+        unsafe { UPSafeCell::new(SyscallStats::new()) };
+}
+
+/// Print the syscall statistics of all the tasks
+pub fn print_syscall_stats() {
+    let stats = SYSCALL_STATS.exclusive_access();
+    println!("[kernel] syscall          count  total(us)    max(us)");
+    for stat in stats.as_slice() {
+        println!(
+            "[kernel] {:<12} {:>9} {:>10} {:>10}",
+            syscall_name(stat.id as usize).unwrap_or("?"),
+            stat.count,
+            stat.total_time,
+            stat.max_time
+        );
+    }
+}
@@ -0,0 +1,104 @@
+//! Implementation of [`TaskManager`]
+use super::scheduler::{Scheduler, SelectedScheduler};
+use super::{TaskControlBlock, TaskStatus};
+use crate::sync::UPSafeCell;
+use alloc::collections::BTreeMap;
+use alloc::sync::Arc;
+use lazy_static::*;
+
+/// A array of `TaskControlBlock` that is thread-safe
+pub struct TaskManager {
+    scheduler: SelectedScheduler,
+}
+
+/// The ready tasks, scheduled by the policy selected by cargo features.
+impl TaskManager {
+    /// Create an empty TaskManager
+    pub fn new() -> Self {
+        Self {
+            scheduler: SelectedScheduler::new(),
+        }
+    }
+    /// Add process back to ready queue
+    pub fn add(&mut self, task: Arc<TaskControlBlock>) {
+        self.scheduler.add(task);
+    }
+    /// Take the process to run next out of the ready queue
+    pub fn fetch(&mut self) -> Option<Arc<TaskControlBlock>> {
+        self.scheduler.pick_next()
+    }
+    /// Account a timer tick to the running process, returns whether to preempt it
+    pub fn tick(&mut self, task: &Arc<TaskControlBlock>) -> bool {
+        self.scheduler.on_tick(task)
+    }
+    /// The running process yields the CPU voluntarily
+    pub fn yield_(&mut self, task: &Arc<TaskControlBlock>) {
+        self.scheduler.on_yield(task);
+    }
+    /// Remove a process from the ready queue if it is there
+    pub fn remove(&mut self, task: &Arc<TaskControlBlock>) {
+        self.scheduler.remove(task);
+    }
+}
+
+lazy_static! {
+    /// TASK_MANAGER instance through lazy_static!
+    pub static ref TASK_MANAGER: UPSafeCell<TaskManager> =
+        unsafe { UPSafeCell::new(TaskManager::new()) };
+}
+
+lazy_static! {
+    /// All the processes which have not been reaped yet, by pid
+    pub static ref PID2TASK: UPSafeCell<BTreeMap<usize, Arc<TaskControlBlock>>> =
+        unsafe { UPSafeCell::new(BTreeMap::new()) };
+}
+
+/// Get the process with `pid`, if it has not been reaped
+pub fn pid2task(pid: usize) -> Option<Arc<TaskControlBlock>> {
+    PID2TASK.exclusive_access().get(&pid).map(Arc::clone)
+}
+
+/// Make a new process findable by its pid
+pub fn insert_into_pid2task(pid: usize, task: Arc<TaskControlBlock>) {
+    PID2TASK.exclusive_access().insert(pid, task);
+}
+
+/// Forget a process being reaped
+pub fn remove_from_pid2task(pid: usize) {
+    if PID2TASK.exclusive_access().remove(&pid).is_none() {
+        panic!("cannot find pid {} in pid2task!", pid);
+    }
+}
+
+/// Whether any process is blocked, waiting for something to wake it up
+pub fn any_task_blocked() -> bool {
+    PID2TASK
+        .exclusive_access()
+        .values()
+        .any(|task| task.inner_exclusive_access().task_status == TaskStatus::Blocked)
+}
+
+/// Add process to ready queue
+pub fn add_task(task: Arc<TaskControlBlock>) {
+    TASK_MANAGER.exclusive_access().add(task);
+}
+
+/// Take a process out of the ready queue
+pub fn fetch_task() -> Option<Arc<TaskControlBlock>> {
+    TASK_MANAGER.exclusive_access().fetch()
+}
+
+/// Account a timer tick to the running process, returns whether to preempt it
+pub fn tick_task(task: &Arc<TaskControlBlock>) -> bool {
+    TASK_MANAGER.exclusive_access().tick(task)
+}
+
+/// Tell the scheduler that the running process yields the CPU voluntarily
+pub fn yield_task(task: &Arc<TaskControlBlock>) {
+    TASK_MANAGER.exclusive_access().yield_(task);
+}
+
+/// Remove a process from the ready queue if it is there
+pub fn remove_task(task: &Arc<TaskControlBlock>) {
+    TASK_MANAGER.exclusive_access().remove(task);
+}
@@ -4,207 +4,256 @@
 //! implemented here.
 //!
 //! A single global instance of [`TaskManager`] called `TASK_MANAGER` controls
-//! all the tasks in the operating system.
+//! all the tasks in the whole operating system.
+//!
+//! A single global instance of [`Processor`] called `PROCESSOR` monitors running
+//! task(s) for each core.
+//!
+//! A single global instance of [`PidAllocator`] called `PID_ALLOCATOR` allocates
+//! pid for user apps.
 //!
 //! Be careful when you see `__switch` ASM function in `switch.S`. Control flow around this function
 //! might not be what you expect.
 
 mod context;
+mod manager;
+mod pid;
+mod processor;
+mod scheduler;
+mod stats;
+mod summary;
 mod switch;
 #[allow(clippy::module_inception)]
 mod task;
 
-use crate::config::MAX_APP_NUM;
-use crate::config::MAX_SYSCALL_NUM;
-use crate::loader::{get_num_app, init_app_cx};
-use crate::sync::UPSafeCell;
-use crate::timer::get_time_ms;
+use crate::heap_alloc::print_heap_stats;
+use crate::loader::get_app_data_by_name;
+use crate::mm::print_frame_stats;
+use crate::sbi::{shutdown, ShutdownReason};
+use crate::syscall::print_syscall_stats;
+use crate::timer::{get_time_us, timer_pending};
+use alloc::sync::Arc;
 use lazy_static::*;
+pub use manager::{fetch_task, TaskManager};
+pub use stats::TaskStats;
+pub use summary::{
+    all_tasks_exited, completion_reason, exited_tasks, live_tasks, print_summary, record_create,
+    record_exit, TaskSummary,
+};
 use switch::__switch;
 pub use task::{TaskControlBlock, TaskStatus};
 
 pub use context::TaskContext;
+pub use manager::{
+    add_task, any_task_blocked, insert_into_pid2task, pid2task, remove_from_pid2task, remove_task,
+    tick_task, yield_task,
+};
+pub use pid::{kernel_stack_position, pid_alloc, KernelStack, PidAllocator, PidHandle};
+pub use processor::{
+    current_task, current_trap_cx, current_user_token, fp_owner, run_tasks, schedule, set_fp_owner,
+    take_current_task, FpOwner, Processor,
+};
 
-/// The task manager, where all the tasks are managed.
-///
-/// Functions implemented on `TaskManager` deals with all task state transitions
-/// and task context switching. For convenience, you can find wrappers around it
-/// in the module level.
-///
-/// Most of `TaskManager` are hidden behind the field `inner`, to defer
-/// borrowing checks to runtime. You can see examples on how to use `inner` in
-/// existing functions on `TaskManager`.
-pub struct TaskManager {
-    /// total number of tasks
-    num_app: usize,
-    /// use inner value to get mutable access
-    inner: UPSafeCell<TaskManagerInner>,
+/// The current 'Running' task gives up the CPU voluntarily, run the next task in task list.
+pub fn yield_current_and_run_next() {
+    let task = current_task().unwrap();
+    task.inner_exclusive_access().stats.voluntary_switches += 1;
+    yield_task(&task);
+    drop(task);
+    suspend_current_and_run_next();
 }
 
-/// Inner of Task Manager
-pub struct TaskManagerInner {
-    /// task list
-    tasks: [TaskControlBlock; MAX_APP_NUM],
-    /// id of current `Running` task
-    current_task: usize,
+/// Preempt the current 'Running' task, run the next task in task list.
+pub fn preempt_current_and_run_next() {
+    current_task()
+        .unwrap()
+        .inner_exclusive_access()
+        .stats
+        .involuntary_switches += 1;
+    suspend_current_and_run_next();
 }
 
-lazy_static! {
-    /// Global variable: TASK_MANAGER
-    pub static ref TASK_MANAGER: TaskManager = {
-        let num_app = get_num_app();
-        let mut tasks = [TaskControlBlock {
-            task_cx: TaskContext::zero_init(),
-            task_status: TaskStatus::UnInit,
-            task_time: 0,
-            task_syscall: [0; MAX_SYSCALL_NUM],
-        }; MAX_APP_NUM];
-        for (i, task) in tasks.iter_mut().enumerate() {
-            task.task_cx = TaskContext::goto_restore(init_app_cx(i));
-            task.task_status = TaskStatus::Ready;
-            task.task_time = get_time_ms() as usize;
-            task.task_syscall = [0; MAX_SYSCALL_NUM];
-        }
-        TaskManager {
-            num_app,
-            inner: unsafe {
-                UPSafeCell::new(TaskManagerInner {
-                    tasks,
-                    current_task: 0,
-                })
-            },
-        }
-    };
+/// Account a timer tick to the current 'Running' task, returns whether it should be preempted.
+pub fn tick_current_task() -> bool {
+    let task = current_task().unwrap();
+    task.inner_exclusive_access().stats.ticks += 1;
+    tick_task(&task)
 }
 
-impl TaskManager {
-    /// Run the first task in task list.
-    ///
-    /// Generally, the first task in task list is an idle task (we call it zero process later).
-    /// But in ch3, we load apps statically, so the first task is a real app.
-    fn run_first_task(&self) -> ! {
-        let mut inner = self.inner.exclusive_access();
-        let task0 = &mut inner.tasks[0];
-        task0.task_status = TaskStatus::Running;
-        let next_task_cx_ptr = &task0.task_cx as *const TaskContext;
-        drop(inner);
-        let mut _unused = TaskContext::zero_init();
-        // before this, we should drop local variables that must be dropped manually
-        unsafe {
-            __switch(&mut _unused as *mut TaskContext, next_task_cx_ptr);
-        }
-        panic!("unreachable in run_first_task!");
-    }
+/// Account the time from the last switch or trap of the current 'Running' task
+/// as user time, on entering the kernel.
+pub fn account_current_trap_enter() {
+    current_task()
+        .unwrap()
+        .inner_exclusive_access()
+        .stats
+        .trap_enter(get_time_us());
+}
 
-    /// Change the status of current `Running` task into `Ready`.
-    fn mark_current_suspended(&self) {
-        let mut inner = self.inner.exclusive_access();
-        let current = inner.current_task;
-        inner.tasks[current].task_status = TaskStatus::Ready;
-    }
+/// Account the time from the last switch or trap of the current 'Running' task
+/// as kernel time, on returning to user mode.
+pub fn account_current_trap_return() {
+    current_task()
+        .unwrap()
+        .inner_exclusive_access()
+        .stats
+        .trap_return(get_time_us());
+}
 
-    /// Change the status of current `Running` task into `Exited`.
-    fn mark_current_exited(&self) {
-        let mut inner = self.inner.exclusive_access();
-        let current = inner.current_task;
-        inner.tasks[current].task_status = TaskStatus::Exited;
-    }
+/// Suspend the current 'Running' task and run the next task in task list.
+pub fn suspend_current_and_run_next() {
+    // There must be an application running.
+    let task = take_current_task().unwrap();
 
-    /// Find next task to run and return task id.
-    ///
-    /// In this case, we only return the first `Ready` task in task list.
-    fn find_next_task(&self) -> Option<usize> {
-        let inner = self.inner.exclusive_access();
-        let current = inner.current_task;
-        (current + 1..current + self.num_app + 1)
-            .map(|id| id % self.num_app)
-            .find(|id| inner.tasks[*id].task_status == TaskStatus::Ready)
-    }
+    // ---- access current TCB exclusively
+    let mut task_inner = task.inner_exclusive_access();
+    let task_cx_ptr = &mut task_inner.task_cx as *mut TaskContext;
+    task_inner.stats.switch_out(get_time_us());
+    // Change status to Ready
+    task_inner.task_status = TaskStatus::Ready;
+    drop(task_inner);
+    // ---- release current PCB
 
-    /// Switch current `Running` task to the task we have found,
-    /// or there is no `Ready` task and we can exit with all applications completed
-    fn run_next_task(&self) {
-        if let Some(next) = self.find_next_task() {
-            let mut inner = self.inner.exclusive_access();
-            let current = inner.current_task;
-            inner.tasks[next].task_status = TaskStatus::Running;
-            inner.current_task = next;
-            let current_task_cx_ptr = &mut inner.tasks[current].task_cx as *mut TaskContext;
-            let next_task_cx_ptr = &inner.tasks[next].task_cx as *const TaskContext;
-            drop(inner);
-            // before this, we should drop local variables that must be dropped manually
-            unsafe {
-                __switch(current_task_cx_ptr, next_task_cx_ptr);
-            }
-            // go back to user mode
-        } else {
-            panic!("All applications completed!");
-        }
-    }
+    // push back to ready queue.
+    add_task(task);
+    // jump to scheduling cycle
+    schedule(task_cx_ptr);
+}
 
-    fn change_syscall_time(&self, syscall_id: usize) {
-        let mut inner = self.inner.exclusive_access();
-        let current = inner.current_task;
-        inner.tasks[current].task_syscall[syscall_id] += 1 ;
-    }
+/// Block the current 'Running' task until someone wakes it up with [`wakeup_task`],
+/// and run the next task in task list.
+///
+/// The caller must have put the task where its waker can find it.
+pub fn block_current_and_run_next() {
+    let task = take_current_task().unwrap();
+    let mut task_inner = task.inner_exclusive_access();
+    let task_cx_ptr = &mut task_inner.task_cx as *mut TaskContext;
+    task_inner.stats.switch_out(get_time_us());
+    task_inner.stats.voluntary_switches += 1;
+    task_inner.task_status = TaskStatus::Blocked;
+    drop(task_inner);
+    // not in the ready queue, only the waker holds it now
+    drop(task);
+    schedule(task_cx_ptr);
+}
+
+/// Make a task blocked by [`block_current_and_run_next`] ready again.
+pub fn wakeup_task(task: Arc<TaskControlBlock>) {
+    let mut task_inner = task.inner_exclusive_access();
+    task_inner.task_status = TaskStatus::Ready;
+    drop(task_inner);
+    add_task(task);
+}
+
+/// pid of the initial process, the first task created, which adopts orphans
+pub const INITPROC_PID: usize = 0;
 
-    fn get_running_task_time(&self) -> usize {
-        let inner = self.inner.exclusive_access();
-        let current = inner.current_task;
-        inner.tasks[current].task_time
+/// Exit the current 'Running' task and run the next task in task list.
+pub fn exit_current_and_run_next(exit_code: i32) {
+    // take from Processor
+    let task = take_current_task().unwrap();
+    task.inner_exclusive_access()
+        .stats
+        .switch_out(get_time_us());
+
+    let pid = task.getpid();
+    record_exit(&task, exit_code);
+    if pid == INITPROC_PID {
+        println!(
+            "[kernel] Initial process exit with exit_code {} ...",
+            exit_code
+        );
+        // nobody is left to adopt the orphans
+        shutdown_with_summary(completion_reason());
     }
 
-    fn get_running_task_syscall(&self) -> [u32; MAX_SYSCALL_NUM] {
-        let inner = self.inner.exclusive_access();
-        let current = inner.current_task;
-        inner.tasks[current].task_syscall
+    // the pid may be reused soon, forget that the FPU holds its state
+    if fp_owner() == FpOwner::Task(pid) {
+        set_fp_owner(FpOwner::Unknown);
     }
-}
+    // make sure the scheduler will never pick it again
+    remove_task(&task);
 
-/// Run the first task in task list.
-pub fn run_first_task() {
-    TASK_MANAGER.run_first_task();
-}
+    // **** access current TCB exclusively
+    let mut inner = task.inner_exclusive_access();
+    // Change status to Zombie
+    inner.task_status = TaskStatus::Zombie;
+    // Record exit code
+    inner.exit_code = exit_code;
+    // do not move to its parent but under initproc
 
-/// Switch current `Running` task to the task we have found,
-/// or there is no `Ready` task and we can exit with all applications completed
-fn run_next_task() {
-    TASK_MANAGER.run_next_task();
-}
+    // ++++++ access initproc TCB exclusively
+    {
+        let mut initproc_inner = INITPROC.inner_exclusive_access();
+        for child in inner.children.iter() {
+            child.inner_exclusive_access().parent = Some(Arc::downgrade(&INITPROC));
+            initproc_inner.children.push(child.clone());
+        }
+    }
+    // ++++++ release parent PCB
 
-/// Change the status of current `Running` task into `Ready`.
-fn mark_current_suspended() {
-    TASK_MANAGER.mark_current_suspended();
+    inner.children.clear();
+    // deallocate user space
+    inner.memory_set.recycle_data_pages();
+    drop(inner);
+    // **** release current PCB
+    // drop task manually to maintain rc correctly
+    drop(task);
+    // we do not have to save task context
+    let mut _unused = TaskContext::zero_init();
+    schedule(&mut _unused as *mut _);
 }
 
-/// Change the status of current `Running` task into `Exited`.
-fn mark_current_exited() {
-    TASK_MANAGER.mark_current_exited();
+/// Whether no task is left to run, when none is ready: either all the tasks
+/// have exited, or none is blocked and no timer is pending to wake one up.
+pub fn nothing_left_to_run() -> bool {
+    all_tasks_exited() || (!any_task_blocked() && !timer_pending())
 }
 
-/// Suspend the current 'Running' task and run the next task in task list.
-pub fn suspend_current_and_run_next() {
-    mark_current_suspended();
-    run_next_task();
+/// Print the summary of all the exited tasks and shut down for `reason`
+pub fn shutdown_with_summary(reason: ShutdownReason) -> ! {
+    print_summary();
+    print_syscall_stats();
+    print_frame_stats();
+    print_heap_stats();
+    #[cfg(feature = "heap_debug")]
+    crate::heap_alloc::dump_live_allocations();
+    println!("[kernel] All applications completed! ({:?})", reason);
+    shutdown(reason)
 }
 
-/// Exit the current 'Running' task and run the next task in task list.
-pub fn exit_current_and_run_next() {
-    mark_current_exited();
-    run_next_task();
+lazy_static! {
+    /// Creation of initial process
+    ///
+    /// the name "initproc" may be changed to any other app name like "usertests",
+    /// but we have user_shell, so we don't need to change it.
+    pub static ref INITPROC: Arc<TaskControlBlock> = Arc::new(
+        TaskControlBlock::new(get_app_data_by_name("ch5b_initproc").unwrap()).unwrap()
+    );
 }
 
-/// get current running task time
-pub fn get_running_task_time() -> usize {
-    TASK_MANAGER.get_running_task_time()
+/// Add init process to the manager
+pub fn add_initproc() {
+    assert_eq!(INITPROC.getpid(), INITPROC_PID);
+    insert_into_pid2task(INITPROC.getpid(), INITPROC.clone());
+    add_task(INITPROC.clone());
 }
 
-/// get current running task syscall
-pub fn get_running_task_syscall() -> [u32; MAX_SYSCALL_NUM] {
-    TASK_MANAGER.get_running_task_syscall()
+/// Count a call of `syscall_id` by the current running task, on entering it
+pub fn record_syscall_enter(syscall_id: usize) {
+    current_task()
+        .unwrap()
+        .inner_exclusive_access()
+        .syscall_stats
+        .enter(syscall_id);
 }
 
-/// chage syscall_id times plus 1
-pub fn change_syscall_time(syscall_id: usize) {
-    TASK_MANAGER.change_syscall_time(syscall_id);
+/// Account `latency` microseconds to a call of `syscall_id` by the current
+/// running task, on returning from it
+pub fn record_syscall_exit(syscall_id: usize, latency: usize) {
+    current_task()
+        .unwrap()
+        .inner_exclusive_access()
+        .syscall_stats
+        .exit(syscall_id, latency);
 }
@@ -0,0 +1,154 @@
+//! Implementation of [`Processor`] and Intersection of control flow
+//!
+//! Here, the continuous operation of user apps in CPU is maintained,
+//! the current running state of CPU is recorded,
+//! and the replacement and transfer of control flow of different applications are executed.
+
+use super::__switch;
+use super::{
+    completion_reason, fetch_task, nothing_left_to_run, shutdown_with_summary, TaskStatus,
+};
+use super::{TaskContext, TaskControlBlock};
+use crate::sync::UPSafeCell;
+use crate::timer::{check_timer, get_time_us, program_next_timer, restart_tick, stop_tick};
+use crate::trap::TrapContext;
+use alloc::sync::Arc;
+use core::arch::asm;
+use lazy_static::*;
+
+/// Whose floating-point state the FP registers of a processor hold
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum FpOwner {
+    /// Nobody's, e.g. left by the bootloader or by an exited task
+    Unknown,
+    /// The initial all-zero state, shared by all tasks which never used the FPU
+    Initial,
+    /// The state of the task with the given pid
+    Task(usize),
+}
+
+/// Processor management structure
+pub struct Processor {
+    /// The task currently executing on the current processor
+    current: Option<Arc<TaskControlBlock>>,
+
+    /// The basic control flow of each core, helping to select and switch process
+    idle_task_cx: TaskContext,
+
+    /// Whose state the FP registers hold, so that FP state is restored lazily
+    fp_owner: FpOwner,
+}
+
+impl Processor {
+    /// Create an empty Processor
+    pub fn new() -> Self {
+        Self {
+            current: None,
+            idle_task_cx: TaskContext::zero_init(),
+            fp_owner: FpOwner::Unknown,
+        }
+    }
+
+    /// Get mutable reference to `idle_task_cx`
+    fn get_idle_task_cx_ptr(&mut self) -> *mut TaskContext {
+        &mut self.idle_task_cx as *mut _
+    }
+
+    /// Get current task in moving semanteme
+    pub fn take_current(&mut self) -> Option<Arc<TaskControlBlock>> {
+        self.current.take()
+    }
+
+    /// Get current task in cloning semanteme
+    pub fn current(&self) -> Option<Arc<TaskControlBlock>> {
+        self.current.as_ref().map(Arc::clone)
+    }
+}
+
+lazy_static! {
+    /// PROCESSOR instance through lazy_static!
+    pub static ref PROCESSOR: UPSafeCell<Processor> = unsafe { UPSafeCell::new(Processor::new()) };
+}
+
+/// The main part of process execution and scheduling
+/// Loop `fetch_task` to get the process that needs to run, and switch the process through `__switch`
+pub fn run_tasks() {
+    loop {
+        let mut processor = PROCESSOR.exclusive_access();
+        if let Some(task) = fetch_task() {
+            let idle_task_cx_ptr = processor.get_idle_task_cx_ptr();
+            // access coming task TCB exclusively
+            let mut task_inner = task.inner_exclusive_access();
+            let next_task_cx_ptr = &task_inner.task_cx as *const TaskContext;
+            task_inner.task_status = TaskStatus::Running;
+            task_inner.stats.dispatch(get_time_us());
+            restart_tick();
+            // release coming task_inner manually
+            drop(task_inner);
+            // release coming task TCB manually
+            processor.current = Some(task);
+            // release processor manually
+            drop(processor);
+            unsafe {
+                __switch(idle_task_cx_ptr, next_task_cx_ptr);
+            }
+        } else {
+            drop(processor);
+            if nothing_left_to_run() {
+                shutdown_with_summary(completion_reason());
+            }
+            // the live tasks are all waiting for something, idle until the next timer event
+            stop_tick();
+            program_next_timer();
+            unsafe {
+                asm!("wfi");
+            }
+            // the interrupt is not taken in S-mode, it is pending until the timer is reprogrammed
+            check_timer();
+        }
+    }
+}
+
+/// Get current task through take, leaving a None in its place
+pub fn take_current_task() -> Option<Arc<TaskControlBlock>> {
+    PROCESSOR.exclusive_access().take_current()
+}
+
+/// Get a copy of the current task
+pub fn current_task() -> Option<Arc<TaskControlBlock>> {
+    PROCESSOR.exclusive_access().current()
+}
+
+/// Get the current user token(addr of page table)
+pub fn current_user_token() -> usize {
+    let task = current_task().unwrap();
+    task.get_user_token()
+}
+
+/// Get the mutable reference to trap context of current task
+pub fn current_trap_cx() -> &'static mut TrapContext {
+    current_task()
+        .unwrap()
+        .inner_exclusive_access()
+        .get_trap_cx()
+}
+
+/// Get whose state the FP registers hold
+pub fn fp_owner() -> FpOwner {
+    PROCESSOR.exclusive_access().fp_owner
+}
+
+/// Record whose state the FP registers hold
+pub fn set_fp_owner(owner: FpOwner) {
+    PROCESSOR.exclusive_access().fp_owner = owner;
+}
+
+/// Return to idle control flow for new scheduling
+pub fn schedule(switched_task_cx_ptr: *mut TaskContext) {
+    let mut processor = PROCESSOR.exclusive_access();
+    let idle_task_cx_ptr = processor.get_idle_task_cx_ptr();
+    drop(processor);
+    unsafe {
+        __switch(switched_task_cx_ptr, idle_task_cx_ptr);
+    }
+}
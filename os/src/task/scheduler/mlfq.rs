@@ -0,0 +1,83 @@
+//! Multi-level feedback queue scheduling
+use super::Scheduler;
+use crate::task::TaskControlBlock;
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+
+/// The number of priority levels, level 0 is the highest
+const LEVELS: usize = 3;
+/// Every this many ticks all tasks are moved back to level 0,
+/// so that CPU-bound tasks at the lowest level do not starve
+const BOOST_INTERVAL: usize = 50;
+
+/// The number of timer ticks a task may run at `level` before being demoted
+fn time_slice(level: usize) -> usize {
+    1 << level
+}
+
+/// A MLFQ scheduler: a task using up its time slice is demoted to a lower
+/// level with a longer slice, while a task yielding early keeps its level
+pub struct MlfqScheduler {
+    ready_queues: [VecDeque<Arc<TaskControlBlock>>; LEVELS],
+    /// ticks since the last priority boost
+    ticks: usize,
+}
+
+impl MlfqScheduler {
+    /// Create an empty MlfqScheduler
+    pub fn new() -> Self {
+        Self {
+            ready_queues: core::array::from_fn(|_| VecDeque::new()),
+            ticks: 0,
+        }
+    }
+    /// Move all the tasks back to level 0
+    fn boost(&mut self, running: &Arc<TaskControlBlock>) {
+        for level in 1..LEVELS {
+            while let Some(task) = self.ready_queues[level].pop_front() {
+                self.ready_queues[0].push_back(task);
+            }
+        }
+        for task in self.ready_queues[0].iter().chain(Some(running)) {
+            let mut inner = task.inner_exclusive_access();
+            inner.mlfq_level = 0;
+            inner.slice_ticks = 0;
+        }
+    }
+}
+
+impl Scheduler for MlfqScheduler {
+    fn add(&mut self, task: Arc<TaskControlBlock>) {
+        let level = task.inner_exclusive_access().mlfq_level;
+        self.ready_queues[level].push_back(task);
+    }
+    fn pick_next(&mut self) -> Option<Arc<TaskControlBlock>> {
+        self.ready_queues
+            .iter_mut()
+            .find_map(|ready_queue| ready_queue.pop_front())
+    }
+    fn on_tick(&mut self, task: &Arc<TaskControlBlock>) -> bool {
+        self.ticks += 1;
+        if self.ticks >= BOOST_INTERVAL {
+            self.ticks = 0;
+            self.boost(task);
+            return true;
+        }
+        let mut inner = task.inner_exclusive_access();
+        inner.slice_ticks += 1;
+        if inner.slice_ticks < time_slice(inner.mlfq_level) {
+            return false;
+        }
+        inner.slice_ticks = 0;
+        inner.mlfq_level = (inner.mlfq_level + 1).min(LEVELS - 1);
+        true
+    }
+    fn on_yield(&mut self, task: &Arc<TaskControlBlock>) {
+        task.inner_exclusive_access().slice_ticks = 0;
+    }
+    fn remove(&mut self, task: &Arc<TaskControlBlock>) {
+        for ready_queue in self.ready_queues.iter_mut() {
+            ready_queue.retain(|t| !Arc::ptr_eq(t, task));
+        }
+    }
+}
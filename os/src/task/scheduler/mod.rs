@@ -0,0 +1,62 @@
+//! Scheduling policies of [`super::TaskManager`]
+//!
+//! The policy is selected at build time by one of the cargo features
+//! `sched_fifo`, `sched_rr`, `sched_mlfq` and `sched_stride`, e.g.
+//! `make run SCHED=rr`. Stride scheduling is used if none is selected.
+use super::TaskControlBlock;
+use alloc::sync::Arc;
+
+/// A scheduling policy, which owns the ready tasks
+pub trait Scheduler {
+    /// Make a task ready to be picked
+    fn add(&mut self, task: Arc<TaskControlBlock>);
+    /// Take the task to run next out of the ready tasks
+    fn pick_next(&mut self) -> Option<Arc<TaskControlBlock>>;
+    /// Account a timer tick to the running `task`,
+    /// returns whether it should be preempted
+    fn on_tick(&mut self, task: &Arc<TaskControlBlock>) -> bool;
+    /// The running `task` gives up the CPU voluntarily
+    fn on_yield(&mut self, task: &Arc<TaskControlBlock>);
+    /// Forget a task, which will not be picked any more
+    fn remove(&mut self, task: &Arc<TaskControlBlock>);
+}
+
+#[cfg(any(
+    all(feature = "sched_fifo", feature = "sched_rr"),
+    all(feature = "sched_fifo", feature = "sched_mlfq"),
+    all(feature = "sched_fifo", feature = "sched_stride"),
+    all(feature = "sched_rr", feature = "sched_mlfq"),
+    all(feature = "sched_rr", feature = "sched_stride"),
+    all(feature = "sched_mlfq", feature = "sched_stride"),
+))]
+compile_error!("select at most one of the sched_* features");
+
+#[cfg(feature = "sched_fifo")]
+mod fifo;
+#[cfg(feature = "sched_fifo")]
+/// The scheduling policy selected by cargo features
+pub use fifo::FifoScheduler as SelectedScheduler;
+
+#[cfg(all(feature = "sched_rr", not(feature = "sched_fifo")))]
+mod rr;
+#[cfg(all(feature = "sched_rr", not(feature = "sched_fifo")))]
+/// The scheduling policy selected by cargo features
+pub use rr::RoundRobinScheduler as SelectedScheduler;
+
+#[cfg(all(
+    feature = "sched_mlfq",
+    not(any(feature = "sched_fifo", feature = "sched_rr"))
+))]
+mod mlfq;
+#[cfg(all(
+    feature = "sched_mlfq",
+    not(any(feature = "sched_fifo", feature = "sched_rr"))
+))]
+/// The scheduling policy selected by cargo features
+pub use mlfq::MlfqScheduler as SelectedScheduler;
+
+#[cfg(not(any(feature = "sched_fifo", feature = "sched_rr", feature = "sched_mlfq")))]
+mod stride;
+#[cfg(not(any(feature = "sched_fifo", feature = "sched_rr", feature = "sched_mlfq")))]
+/// The scheduling policy selected by cargo features
+pub use stride::StrideScheduler as SelectedScheduler;
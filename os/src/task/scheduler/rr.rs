@@ -0,0 +1,46 @@
+//! Round-robin scheduling
+use super::Scheduler;
+use crate::task::TaskControlBlock;
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+
+/// The number of timer ticks a task may run before being preempted
+const TIME_SLICE: usize = 1;
+
+/// A round-robin scheduler with a fixed time slice
+pub struct RoundRobinScheduler {
+    ready_queue: VecDeque<Arc<TaskControlBlock>>,
+}
+
+impl RoundRobinScheduler {
+    /// Create an empty RoundRobinScheduler
+    pub fn new() -> Self {
+        Self {
+            ready_queue: VecDeque::new(),
+        }
+    }
+}
+
+impl Scheduler for RoundRobinScheduler {
+    fn add(&mut self, task: Arc<TaskControlBlock>) {
+        self.ready_queue.push_back(task);
+    }
+    fn pick_next(&mut self) -> Option<Arc<TaskControlBlock>> {
+        self.ready_queue.pop_front()
+    }
+    fn on_tick(&mut self, task: &Arc<TaskControlBlock>) -> bool {
+        let mut inner = task.inner_exclusive_access();
+        inner.slice_ticks += 1;
+        if inner.slice_ticks < TIME_SLICE {
+            return false;
+        }
+        inner.slice_ticks = 0;
+        true
+    }
+    fn on_yield(&mut self, task: &Arc<TaskControlBlock>) {
+        task.inner_exclusive_access().slice_ticks = 0;
+    }
+    fn remove(&mut self, task: &Arc<TaskControlBlock>) {
+        self.ready_queue.retain(|t| !Arc::ptr_eq(t, task));
+    }
+}
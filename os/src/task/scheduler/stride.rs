@@ -0,0 +1,56 @@
+//! Stride scheduling
+use super::Scheduler;
+use crate::task::TaskControlBlock;
+use alloc::collections::VecDeque;
+use alloc::sync::Arc;
+
+/// A stride scheduler, the ready task with the smallest stride runs next,
+/// so that a task gets CPU time in proportion to its priority
+pub struct StrideScheduler {
+    ready_queue: VecDeque<Arc<TaskControlBlock>>,
+}
+
+impl StrideScheduler {
+    /// Create an empty StrideScheduler
+    pub fn new() -> Self {
+        Self {
+            ready_queue: VecDeque::new(),
+        }
+    }
+}
+
+/// Compare strides which may have overflowed.
+///
+/// As the priority is at least 2, every pass is at most `BIG_STRIDE / 2`, so
+/// the strides of ready tasks never differ by more than `BIG_STRIDE / 2` and
+/// their wrapping difference tells their order.
+fn stride_less(a: usize, b: usize) -> bool {
+    (a.wrapping_sub(b) as isize) < 0
+}
+
+impl Scheduler for StrideScheduler {
+    fn add(&mut self, task: Arc<TaskControlBlock>) {
+        self.ready_queue.push_back(task);
+    }
+    /// Take the task with the smallest stride, and advance its stride by its pass
+    fn pick_next(&mut self) -> Option<Arc<TaskControlBlock>> {
+        let (index, _) = self
+            .ready_queue
+            .iter()
+            .map(|task| task.inner_exclusive_access().stride)
+            .enumerate()
+            .reduce(|min, cur| if stride_less(cur.1, min.1) { cur } else { min })?;
+        let task = self.ready_queue.remove(index)?;
+        let mut inner = task.inner_exclusive_access();
+        inner.stride = inner.stride.wrapping_add(inner.pass);
+        drop(inner);
+        Some(task)
+    }
+    fn on_tick(&mut self, _task: &Arc<TaskControlBlock>) -> bool {
+        true
+    }
+    fn on_yield(&mut self, _task: &Arc<TaskControlBlock>) {}
+    fn remove(&mut self, task: &Arc<TaskControlBlock>) {
+        self.ready_queue.retain(|t| !Arc::ptr_eq(t, task));
+    }
+}
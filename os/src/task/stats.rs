@@ -0,0 +1,52 @@
+//! Per-task CPU accounting
+//!
+//! The time of a task is split at the points where it enters or leaves the
+//! kernel or the CPU: [`TaskStats::dispatch`] and [`TaskStats::switch_out`]
+//! around `__switch`, [`TaskStats::trap_enter`] and [`TaskStats::trap_return`]
+//! around the trampoline. All times are in microseconds.
+
+/// CPU usage of a task
+#[derive(Copy, Clone, Default)]
+pub struct TaskStats {
+    /// When the task first ran, `None` before it is dispatched
+    pub first_dispatch_time: Option<usize>,
+    /// Time spent running in user mode
+    pub user_time: usize,
+    /// Time spent running in the kernel on behalf of the task
+    pub kernel_time: usize,
+    /// Times the task gave up the CPU by itself, by yielding or blocking
+    pub voluntary_switches: usize,
+    /// Times the task was preempted
+    pub involuntary_switches: usize,
+    /// Timer ticks which interrupted the task
+    pub ticks: usize,
+    /// Start of the interval not accounted yet
+    last_time: usize,
+}
+
+impl TaskStats {
+    /// The task is switched to at `now`
+    pub fn dispatch(&mut self, now: usize) {
+        self.first_dispatch_time.get_or_insert(now);
+        self.last_time = now;
+    }
+    /// The task is switched away from at `now`
+    pub fn switch_out(&mut self, now: usize) {
+        self.kernel_time += now - self.last_time;
+        self.last_time = now;
+    }
+    /// The task traps into the kernel at `now`
+    pub fn trap_enter(&mut self, now: usize) {
+        self.user_time += now - self.last_time;
+        self.last_time = now;
+    }
+    /// The task returns to user mode at `now`
+    pub fn trap_return(&mut self, now: usize) {
+        self.kernel_time += now - self.last_time;
+        self.last_time = now;
+    }
+    /// Time since the task first ran
+    pub fn elapsed(&self, now: usize) -> usize {
+        self.first_dispatch_time.map_or(0, |first| now - first)
+    }
+}
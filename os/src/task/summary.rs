@@ -0,0 +1,116 @@
+//! Bookkeeping of live and exited tasks, printed when the kernel shuts down
+use super::{TaskControlBlock, TaskStats};
+use crate::sbi::ShutdownReason;
+use crate::sync::UPSafeCell;
+use crate::syscall::{syscall_name, SyscallStats};
+use crate::timer::get_time_us;
+use alloc::string::String;
+use alloc::vec::Vec;
+use core::fmt::Write;
+use lazy_static::*;
+
+/// What is left of a task after it exited
+pub struct TaskSummary {
+    /// Process identifier
+    pub pid: usize,
+    /// Exit code of the task
+    pub exit_code: i32,
+    /// CPU accounting at exit
+    pub stats: TaskStats,
+    /// Microseconds from the first dispatch to exit
+    pub runtime: usize,
+    /// Statistics of the syscalls the task called
+    pub syscall_stats: SyscallStats,
+}
+
+/// The number of live tasks and the summaries of exited ones
+pub struct TaskRecords {
+    live: usize,
+    exited: Vec<TaskSummary>,
+}
+
+lazy_static! {
+    /// TASK_RECORDS instance through lazy_static!
+    pub static ref TASK_RECORDS: UPSafeCell<TaskRecords> = unsafe {
+        UPSafeCell::new(TaskRecords {
+            live: 0,
+            exited: Vec::new(),
+        })
+    };
+}
+
+/// Count a newly created task as live
+pub fn record_create() {
+    TASK_RECORDS.exclusive_access().live += 1;
+}
+
+/// Record the summary of an exiting task
+pub fn record_exit(task: &TaskControlBlock, exit_code: i32) {
+    let inner = task.inner_exclusive_access();
+    let summary = TaskSummary {
+        pid: task.getpid(),
+        exit_code,
+        stats: inner.stats,
+        runtime: inner.stats.elapsed(get_time_us()),
+        syscall_stats: inner.syscall_stats.clone(),
+    };
+    drop(inner);
+    let mut records = TASK_RECORDS.exclusive_access();
+    records.live -= 1;
+    records.exited.push(summary);
+}
+
+/// Whether all the created tasks have exited
+pub fn all_tasks_exited() -> bool {
+    TASK_RECORDS.exclusive_access().live == 0
+}
+
+/// The number of created tasks which have not exited yet
+pub fn live_tasks() -> usize {
+    TASK_RECORDS.exclusive_access().live
+}
+
+/// The number of tasks which have exited
+pub fn exited_tasks() -> usize {
+    TASK_RECORDS.exclusive_access().exited.len()
+}
+
+/// Why the kernel shuts down once the tasks are done: an application failed
+/// if any task exited with a non-zero exit code
+pub fn completion_reason() -> ShutdownReason {
+    let records = TASK_RECORDS.exclusive_access();
+    if records.exited.iter().any(|summary| summary.exit_code != 0) {
+        ShutdownReason::AppFailure
+    } else {
+        ShutdownReason::Success
+    }
+}
+
+/// Print a line for every exited task, in the order of exiting
+pub fn print_summary() {
+    let records = TASK_RECORDS.exclusive_access();
+    println!(
+        "[kernel] {} task(s) exited, {} still alive",
+        records.exited.len(),
+        records.live
+    );
+    println!("[kernel]   pid exit_code runtime(us)  user(us) kernel(us) switches syscalls");
+    for summary in records.exited.iter() {
+        let mut syscalls = String::new();
+        for stat in summary.syscall_stats.as_slice() {
+            let name = syscall_name(stat.id as usize).unwrap_or("?");
+            write!(syscalls, " {}:{}", name, stat.count).unwrap();
+        }
+        let stats = &summary.stats;
+        println!(
+            "[kernel] {:>5} {:>9} {:>11} {:>9} {:>10} {:>8}{}",
+            summary.pid,
+            summary.exit_code,
+            summary.runtime,
+            stats.user_time,
+            stats.kernel_time,
+            stats.voluntary_switches + stats.involuntary_switches,
+            syscalls
+        );
+    }
+}
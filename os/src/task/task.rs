@@ -1,29 +1,311 @@
-//! Types related to task management
+//! Types related to task management & Functions for completely changing TCB
 
 use super::TaskContext;
-use crate::config::MAX_SYSCALL_NUM;
-/// The task control block (TCB) of a task.
-#[derive(Copy, Clone)]
+use super::{insert_into_pid2task, pid_alloc, record_create, KernelStack, PidHandle, TaskStats};
+use crate::config::{BIG_STRIDE, DEFAULT_PRIORITY, TRAP_CONTEXT_BASE};
+use crate::mm::{MemorySet, PhysPageNum, VirtAddr, KERNEL_SPACE};
+use crate::sync::UPSafeCell;
+use crate::syscall::SyscallStats;
+use crate::trap::{trap_handler, TrapContext};
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::cell::RefMut;
+
+/// Task control block structure
+///
+/// Directly save the contents that will not change during running
 pub struct TaskControlBlock {
-    /// The task status in it's lifecycle
-    pub task_status: TaskStatus,
-    /// The task context
+    // Immutable
+    /// Process identifier
+    pub pid: PidHandle,
+
+    /// Kernel stack corresponding to PID
+    pub kernel_stack: KernelStack,
+
+    /// Mutable
+    inner: UPSafeCell<TaskControlBlockInner>,
+}
+
+impl TaskControlBlock {
+    /// Get the mutable reference of the inner TCB
+    pub fn inner_exclusive_access(&self) -> RefMut<'_, TaskControlBlockInner> {
+        self.inner.exclusive_access()
+    }
+
+    /// Get the address of app's page table
+    pub fn get_user_token(&self) -> usize {
+        let inner = self.inner_exclusive_access();
+        inner.memory_set.token()
+    }
+}
+
+/// Structure containing more process content
+///
+/// Store the contents that will change during operation
+/// and are wrapped by UPSafeCell to provide mutual exclusion
+pub struct TaskControlBlockInner {
+    /// The physical page number of the frame where the trap context is placed
+    pub trap_cx_ppn: PhysPageNum,
+
+    /// Application data can only appear in areas
+    /// where the application address space is lower than base_size
+    pub base_size: usize,
+
+    /// Save task context
     pub task_cx: TaskContext,
-    /// First time to be called
-    pub task_time: usize, 
-    /// syscall times
-    pub task_syscall: [u32; MAX_SYSCALL_NUM]
+
+    /// Maintain the execution status of the current process
+    pub task_status: TaskStatus,
+
+    /// Application address space
+    pub memory_set: MemorySet,
+
+    /// Parent process of the current process.
+    /// Weak will not affect the reference count of the parent
+    pub parent: Option<Weak<TaskControlBlock>>,
+
+    /// A vector containing TCBs of all child processes of the current process
+    pub children: Vec<Arc<TaskControlBlock>>,
+
+    /// It is set when active exit or execution error occurs
+    pub exit_code: i32,
+
+    /// CPU accounting
+    pub stats: TaskStats,
+
+    /// Count and latency of the syscalls called
+    pub syscall_stats: SyscallStats,
+
+    /// Scheduling priority, at least 2
+    pub priority: usize,
+
+    /// The accumulated stride, the task with the smallest one runs next
+    pub stride: usize,
+
+    /// The stride added each time the task is scheduled, BIG_STRIDE / priority
+    pub pass: usize,
+
+    /// Timer ticks used in the current time slice, for RR and MLFQ scheduling
+    pub slice_ticks: usize,
+
+    /// Queue level in MLFQ scheduling, 0 is the highest
+    pub mlfq_level: usize,
+
+    /// The start of the heap, right after the loaded image
+    pub heap_bottom: usize,
+
+    /// The program break, the end of the heap
+    pub program_brk: usize,
+}
+
+impl TaskControlBlockInner {
+    /// get the trap context
+    pub fn get_trap_cx(&self) -> &'static mut TrapContext {
+        self.trap_cx_ppn.get_mut()
+    }
+    /// get the user token
+    pub fn get_user_token(&self) -> usize {
+        self.memory_set.token()
+    }
+    fn get_status(&self) -> TaskStatus {
+        self.task_status
+    }
+    /// Whether the process has exited and waits to be collected by its parent
+    pub fn is_zombie(&self) -> bool {
+        self.get_status() == TaskStatus::Zombie
+    }
+    /// set the priority, and so the pass, of the process
+    pub fn set_priority(&mut self, priority: usize) {
+        self.priority = priority;
+        self.pass = BIG_STRIDE / priority;
+    }
+    /// Move the program break by `size` bytes, mapping or unmapping the heap
+    /// pages accordingly. Returns the old program break, or `None` if the
+    /// heap would leave the range reserved for it or can not grow.
+    pub fn change_program_brk(&mut self, size: isize) -> Option<usize> {
+        let old_break = self.program_brk;
+        let new_brk = old_break.checked_add_signed(size)?;
+        let (_, heap_limit) = self.memory_set.heap_range()?;
+        if new_brk < self.heap_bottom || new_brk > heap_limit {
+            return None;
+        }
+        let result = if size < 0 {
+            self.memory_set
+                .shrink_to(VirtAddr(self.heap_bottom), VirtAddr(new_brk))
+        } else {
+            self.memory_set
+                .append_to(VirtAddr(self.heap_bottom), VirtAddr(new_brk))
+        };
+        if result {
+            self.program_brk = new_brk;
+            Some(old_break)
+        } else {
+            None
+        }
+    }
+}
+
+impl TaskControlBlock {
+    /// Create a new process
+    ///
+    /// At present, it is only used for the creation of initproc
+    pub fn new(elf_data: &[u8]) -> Option<Self> {
+        // memory_set with elf program headers/trampoline/trap context/user stack
+        let (memory_set, user_sp, entry_point) = MemorySet::from_elf(elf_data)?;
+        let (heap_bottom, _) = memory_set.heap_range().unwrap();
+        let trap_cx_ppn = memory_set
+            .translate(VirtAddr::from(TRAP_CONTEXT_BASE).into())
+            .unwrap()
+            .ppn();
+        // alloc a pid and a kernel stack in kernel space
+        let pid_handle = pid_alloc();
+        let kernel_stack = KernelStack::new(&pid_handle);
+        let kernel_stack_top = kernel_stack.get_top();
+        // push a task context which goes to trap_return to the top of kernel stack
+        let task_control_block = Self {
+            pid: pid_handle,
+            kernel_stack,
+            inner: unsafe {
+                UPSafeCell::new(TaskControlBlockInner {
+                    trap_cx_ppn,
+                    base_size: user_sp,
+                    task_cx: TaskContext::goto_trap_return(kernel_stack_top),
+                    task_status: TaskStatus::Ready,
+                    memory_set,
+                    parent: None,
+                    children: Vec::new(),
+                    exit_code: 0,
+                    stats: TaskStats::default(),
+                    syscall_stats: SyscallStats::new(),
+                    priority: DEFAULT_PRIORITY,
+                    stride: 0,
+                    pass: BIG_STRIDE / DEFAULT_PRIORITY,
+                    slice_ticks: 0,
+                    mlfq_level: 0,
+                    heap_bottom,
+                    program_brk: heap_bottom,
+                })
+            },
+        };
+        // prepare TrapContext in user space
+        let trap_cx = task_control_block.inner_exclusive_access().get_trap_cx();
+        *trap_cx = TrapContext::app_init_context(
+            entry_point,
+            user_sp,
+            KERNEL_SPACE.exclusive_access().token(),
+            kernel_stack_top,
+            trap_handler as usize,
+        );
+        record_create();
+        Some(task_control_block)
+    }
+
+    /// Load a new elf to replace the original application address space and start execution.
+    /// Returns `false` and keeps the original address space if `elf_data` can not be loaded.
+    pub fn exec(&self, elf_data: &[u8]) -> bool {
+        // memory_set with elf program headers/trampoline/trap context/user stack
+        let Some((memory_set, user_sp, entry_point)) = MemorySet::from_elf(elf_data) else {
+            return false;
+        };
+        let (heap_bottom, _) = memory_set.heap_range().unwrap();
+        let trap_cx_ppn = memory_set
+            .translate(VirtAddr::from(TRAP_CONTEXT_BASE).into())
+            .unwrap()
+            .ppn();
+
+        // **** access current TCB exclusively
+        let mut inner = self.inner_exclusive_access();
+        // substitute memory_set
+        inner.memory_set = memory_set;
+        // update trap_cx ppn
+        inner.trap_cx_ppn = trap_cx_ppn;
+        // initialize base_size
+        inner.base_size = user_sp;
+        // the new heap is empty
+        inner.heap_bottom = heap_bottom;
+        inner.program_brk = heap_bottom;
+        // initialize trap_cx
+        let trap_cx = inner.get_trap_cx();
+        *trap_cx = TrapContext::app_init_context(
+            entry_point,
+            user_sp,
+            KERNEL_SPACE.exclusive_access().token(),
+            self.kernel_stack.get_top(),
+            trap_handler as usize,
+        );
+        true
+        // **** release inner automatically
+    }
+
+    /// parent process fork the child process
+    pub fn fork(self: &Arc<Self>) -> Arc<Self> {
+        // ---- access parent PCB exclusively
+        let mut parent_inner = self.inner_exclusive_access();
+        // copy user space(include trap context)
+        let memory_set = MemorySet::from_existed_user(&parent_inner.memory_set);
+        let trap_cx_ppn = memory_set
+            .translate(VirtAddr::from(TRAP_CONTEXT_BASE).into())
+            .unwrap()
+            .ppn();
+        // alloc a pid and a kernel stack in kernel space
+        let pid_handle = pid_alloc();
+        let kernel_stack = KernelStack::new(&pid_handle);
+        let kernel_stack_top = kernel_stack.get_top();
+        let task_control_block = Arc::new(TaskControlBlock {
+            pid: pid_handle,
+            kernel_stack,
+            inner: unsafe {
+                UPSafeCell::new(TaskControlBlockInner {
+                    trap_cx_ppn,
+                    base_size: parent_inner.base_size,
+                    task_cx: TaskContext::goto_trap_return(kernel_stack_top),
+                    task_status: TaskStatus::Ready,
+                    memory_set,
+                    parent: Some(Arc::downgrade(self)),
+                    children: Vec::new(),
+                    exit_code: 0,
+                    stats: TaskStats::default(),
+                    syscall_stats: SyscallStats::new(),
+                    // the child competes from where the parent is, rather than from 0
+                    priority: parent_inner.priority,
+                    stride: parent_inner.stride,
+                    pass: parent_inner.pass,
+                    slice_ticks: 0,
+                    mlfq_level: 0,
+                    heap_bottom: parent_inner.heap_bottom,
+                    program_brk: parent_inner.program_brk,
+                })
+            },
+        });
+        // add child
+        parent_inner.children.push(task_control_block.clone());
+        // modify kernel_sp in trap_cx
+        // **** access child PCB exclusively
+        let trap_cx = task_control_block.inner_exclusive_access().get_trap_cx();
+        trap_cx.kernel_sp = kernel_stack_top;
+        record_create();
+        insert_into_pid2task(task_control_block.getpid(), task_control_block.clone());
+        // return
+        task_control_block
+        // **** release child PCB
+        // ---- release parent PCB
+    }
+
+    /// get pid of process
+    pub fn getpid(&self) -> usize {
+        self.pid.0
+    }
 }
 
-/// The status of a task
 #[derive(Copy, Clone, PartialEq)]
+/// task status: Ready, Running, Blocked, Zombie
 pub enum TaskStatus {
-    /// uninitialized
-    UnInit,
     /// ready to run
     Ready,
     /// running
     Running,
-    /// exited
-    Exited,
+    /// waiting for an event, e.g. console input
+    Blocked,
+    /// exited, waiting for the parent to collect its exit code
+    Zombie,
 }
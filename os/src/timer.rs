@@ -1,9 +1,17 @@
 //! RISC-V timer-related functionality
 
-use crate::config::CLOCK_FREQ;
+use crate::fdt::clock_freq;
 use crate::sbi::set_timer;
+use crate::sync::UPSafeCell;
+use crate::task::{wakeup_task, TaskControlBlock};
+use alloc::boxed::Box;
+use alloc::collections::BinaryHeap;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::cmp::Ordering;
+use lazy_static::*;
 use riscv::register::time;
-/// The number of ticks per second
+/// The number of ticks per second while a task is running
 const TICKS_PER_SEC: usize = 100;
 #[allow(dead_code)]
 /// The number of milliseconds per second
@@ -20,16 +28,165 @@ pub fn get_time() -> usize {
 /// get current time in milliseconds
 #[allow(dead_code)]
 pub fn get_time_ms() -> usize {
-    time::read() * MSEC_PER_SEC / CLOCK_FREQ
+    time::read() * MSEC_PER_SEC / clock_freq()
 }
 
 /// get current time in microseconds
 #[allow(dead_code)]
 pub fn get_time_us() -> usize {
-    time::read() * MICRO_PER_SEC / CLOCK_FREQ
+    time::read() * MICRO_PER_SEC / clock_freq()
 }
 
-/// Set the next timer interrupt
-pub fn set_next_trigger() {
-    set_timer(get_time() + CLOCK_FREQ / TICKS_PER_SEC);
+/// The length of a tick, the unit of time slices, in timer ticks
+fn tick_len() -> usize {
+    clock_freq() / TICKS_PER_SEC
+}
+
+/// What to do when a timer expires
+enum TimerAction {
+    /// Wake up a blocked task
+    Wakeup(Arc<TaskControlBlock>),
+    /// Call back a kernel function
+    Callback(Box<dyn FnOnce() + Send>),
+}
+
+/// An event at `expire`
+pub struct TimerEvent {
+    /// The time of the event, in timer ticks
+    pub expire: usize,
+    action: TimerAction,
+}
+
+impl PartialEq for TimerEvent {
+    fn eq(&self, other: &Self) -> bool {
+        self.expire == other.expire
+    }
+}
+impl Eq for TimerEvent {}
+impl PartialOrd for TimerEvent {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for TimerEvent {
+    fn cmp(&self, other: &Self) -> Ordering {
+        // reversed, so that the BinaryHeap pops the earliest first
+        other.expire.cmp(&self.expire)
+    }
+}
+
+/// The timer is programmed for the earliest of the end of the current tick
+/// of the running task, and the pending events.
+struct Timers {
+    /// The end of the current tick, `None` if no task is running
+    tick_end: Option<usize>,
+    /// The pending events
+    events: BinaryHeap<TimerEvent>,
+}
+
+lazy_static! {
+    static ref TIMERS: UPSafeCell<Timers> = unsafe {
+        UPSafeCell::new(Timers {
+            tick_end: None,
+            events: BinaryHeap::new(),
+        })
+    };
+}
+
+/// Wake up `task` at `expire`, it must be blocked by the caller
+pub fn add_timer(expire: usize, task: Arc<TaskControlBlock>) {
+    let action = TimerAction::Wakeup(task);
+    TIMERS
+        .exclusive_access()
+        .events
+        .push(TimerEvent { expire, action });
+}
+
+/// Call `callback` at `expire`. It runs in the kernel with the trapped task,
+/// if any, as the current task, so it must not block or switch tasks.
+pub fn add_kernel_timer(expire: usize, callback: impl FnOnce() + Send + 'static) {
+    let action = TimerAction::Callback(Box::new(callback));
+    TIMERS
+        .exclusive_access()
+        .events
+        .push(TimerEvent { expire, action });
+}
+
+/// Whether any event is pending, which may wake up a task later
+pub fn timer_pending() -> bool {
+    !TIMERS.exclusive_access().events.is_empty()
+}
+
+/// Fire the events whose time has come
+pub fn check_timer() {
+    let current = get_time();
+    let mut due = Vec::new();
+    let mut timers = TIMERS.exclusive_access();
+    while timers
+        .events
+        .peek()
+        .is_some_and(|event| event.expire <= current)
+    {
+        due.push(timers.events.pop().unwrap());
+    }
+    // callbacks may add timers
+    drop(timers);
+    for event in due {
+        match event.action {
+            TimerAction::Wakeup(task) => wakeup_task(task),
+            TimerAction::Callback(callback) => callback(),
+        }
+    }
+}
+
+/// Start a tick for the task about to run, unless one is going on
+pub fn start_tick() {
+    TIMERS
+        .exclusive_access()
+        .tick_end
+        .get_or_insert_with(|| get_time() + tick_len());
+}
+
+/// Start a full tick for the task being dispatched, dropping what is left of
+/// the tick of the task before it
+pub fn restart_tick() {
+    TIMERS.exclusive_access().tick_end = Some(get_time() + tick_len());
+}
+
+/// Stop ticking, as no task is running
+pub fn stop_tick() {
+    TIMERS.exclusive_access().tick_end = None;
+}
+
+/// Whether the current tick is over, which ends it
+pub fn tick_expired() -> bool {
+    let mut timers = TIMERS.exclusive_access();
+    match timers.tick_end {
+        Some(tick_end) if tick_end <= get_time() => {
+            timers.tick_end = None;
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Program the timer interrupt for the earliest of the end of the current
+/// tick and the pending events, or disable it if there is none of them
+pub fn program_next_timer() {
+    let timers = TIMERS.exclusive_access();
+    let next = timers
+        .tick_end
+        .into_iter()
+        .chain(timers.events.peek().map(|event| event.expire))
+        .min();
+    set_timer(next.unwrap_or(usize::MAX));
+}
+
+/// Convert a duration of `sec` seconds and `nsec` nanoseconds to ticks, rounding up
+pub fn duration_to_ticks(sec: usize, nsec: usize) -> usize {
+    const NSEC_PER_SEC: usize = 1_000_000_000;
+    let freq = clock_freq();
+    sec.saturating_mul(freq)
+        .saturating_add((nsec * freq).div_ceil(NSEC_PER_SEC))
 }
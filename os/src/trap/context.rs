@@ -1,4 +1,9 @@
-use riscv::register::sstatus::{self, Sstatus, SPP};
+//! Implementation of [`TrapContext`]
+use riscv::register::sstatus::{self, Sstatus, FS, SPP};
+
+extern "C" {
+    fn __load_fp(fp: *const usize);
+}
 
 #[repr(C)]
 #[derive(Debug)]
@@ -10,6 +15,16 @@ pub struct TrapContext {
     pub sstatus: Sstatus,
     /// Supervisor Exception Program Counter
     pub sepc: usize,
+    /// Token of kernel address space
+    pub kernel_satp: usize,
+    /// Kernel stack pointer of the current application
+    pub kernel_sp: usize,
+    /// Virtual address of trap handler entry point in kernel
+    pub trap_handler: usize,
+    /// Floating-Point Register f0-31, saved only if the application dirtied them
+    pub f: [usize; 32],
+    /// Floating-Point Control and Status Register
+    pub fcsr: usize,
 }
 
 impl TrapContext {
@@ -18,15 +33,36 @@ impl TrapContext {
         self.x[2] = sp;
     }
     /// init the trap context of an application
-    pub fn app_init_context(entry: usize, sp: usize) -> Self {
+    pub fn app_init_context(
+        entry: usize,
+        sp: usize,
+        kernel_satp: usize,
+        kernel_sp: usize,
+        trap_handler: usize,
+    ) -> Self {
+        unsafe {
+            sstatus::set_fs(FS::Initial); // FPU enabled, but never used by the app
+        }
         let mut sstatus = sstatus::read(); // CSR sstatus
         sstatus.set_spp(SPP::User); //previous privilege mode: user mode
         let mut cx = Self {
             x: [0; 32],
             sstatus,
-            sepc: entry, // entry point of app
+            sepc: entry,  // entry point of app
+            kernel_satp,  // addr of page table
+            kernel_sp,    // kernel stack
+            trap_handler, // addr of trap_handler function
+            f: [0; 32],
+            fcsr: 0,
         };
         cx.set_sp(sp); // app's user stack pointer
         cx // return initial Trap Context of app
     }
+    /// Load f0-31 and fcsr saved in this context into the FPU
+    pub fn load_fp(&self) {
+        unsafe {
+            sstatus::set_fs(FS::Clean);
+            __load_fp(self.f.as_ptr());
+        }
+    }
 }
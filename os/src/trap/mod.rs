@@ -6,7 +6,9 @@
 //! All traps go through `__alltraps`, which is defined in `trap.S`. The
 //! assembly language code does just enough work restore the kernel space
 //! context, ensuring that Rust code safely runs, and transfers control to
-//! [`trap_handler()`].
+//! [`trap_handler()`]. As `__alltraps` and `__restore` switch between the
+//! user and kernel address spaces, they live in the trampoline page, which is
+//! mapped at the same virtual address [`TRAMPOLINE`] in every address space.
 //!
 //! It then calls different functionality based on what exactly the exception
 //! was. For example, timer interrupts trigger task preemption, and syscalls go
@@ -14,25 +16,40 @@
 
 mod context;
 
+use crate::config::{TRAMPOLINE, TRAP_CONTEXT_BASE};
 use crate::syscall::syscall;
-use crate::task::{exit_current_and_run_next, suspend_current_and_run_next};
-use crate::timer::set_next_trigger;
-use core::arch::global_asm;
+use crate::task::{
+    account_current_trap_enter, account_current_trap_return, current_task, current_trap_cx,
+    current_user_token, exit_current_and_run_next, fp_owner, preempt_current_and_run_next,
+    set_fp_owner, tick_current_task, FpOwner,
+};
+use crate::timer::{check_timer, program_next_timer, start_tick, tick_expired};
+use core::arch::{asm, global_asm};
 use riscv::register::{
     mtvec::TrapMode,
     scause::{self, Exception, Interrupt, Trap},
-    sie, stval, stvec,
+    sie,
+    sstatus::FS,
+    stval, stvec,
 };
 
 global_asm!(include_str!("trap.S"));
+global_asm!(include_str!("fp.S"));
 
 /// Initialize trap handling
 pub fn init() {
-    extern "C" {
-        fn __alltraps();
+    set_kernel_trap_entry();
+}
+
+fn set_kernel_trap_entry() {
+    unsafe {
+        stvec::write(trap_from_kernel as usize, TrapMode::Direct);
     }
+}
+
+fn set_user_trap_entry() {
     unsafe {
-        stvec::write(__alltraps as usize, TrapMode::Direct);
+        stvec::write(TRAMPOLINE, TrapMode::Direct);
     }
 }
 
@@ -43,30 +60,65 @@ pub fn enable_timer_interrupt() {
     }
 }
 
+/// Exit code of an application killed by an exception with code `code`.
+///
+/// Every exception gets its own exit code, `-(16 + code)`, so that the parent
+/// can tell from `waitpid` which exception killed its child.
+fn user_exception_exit_code(code: usize) -> i32 {
+    -16 - code as i32
+}
+
+/// The owner of the FP registers expected by the current task, which has
+/// `cx` as its trap context
+fn expected_fp_owner(cx: &TrapContext) -> FpOwner {
+    match cx.sstatus.fs() {
+        // the app has never touched the FPU
+        FS::Off | FS::Initial => FpOwner::Initial,
+        _ => FpOwner::Task(current_task().unwrap().getpid()),
+    }
+}
+
 /// trap handler
 #[no_mangle]
-pub fn trap_handler(cx: &mut TrapContext) -> &mut TrapContext {
+pub fn trap_handler() -> ! {
+    set_kernel_trap_entry();
+    account_current_trap_enter();
+    // the FPU still holds the state of the trapped task, `__alltraps` has saved it if dirty
+    set_fp_owner(expected_fp_owner(current_trap_cx()));
     let scause = scause::read(); // get trap cause
     let stval = stval::read(); // get extra value
                                // trace!("into {:?}", scause.cause());
     match scause.cause() {
         Trap::Exception(Exception::UserEnvCall) => {
             // jump to next instruction anyway
+            let mut cx = current_trap_cx();
             cx.sepc += 4;
             // get system call return value
-            cx.x[10] = syscall(cx.x[17], [cx.x[10], cx.x[11], cx.x[12]]) as usize;
-        }
-        Trap::Exception(Exception::StoreFault) | Trap::Exception(Exception::StorePageFault) => {
-            println!("[kernel] PageFault in application, bad addr = {:#x}, bad instruction = {:#x}, kernel killed it.", stval, cx.sepc);
-            exit_current_and_run_next();
+            let result = syscall(
+                cx.x[17],
+                [cx.x[10], cx.x[11], cx.x[12], cx.x[13], cx.x[14], cx.x[15]],
+            );
+            // cx is changed during sys_exec, so we have to call it again
+            cx = current_trap_cx();
+            cx.x[10] = result as usize;
         }
-        Trap::Exception(Exception::IllegalInstruction) => {
-            println!("[kernel] IllegalInstruction in application, kernel killed it.");
-            exit_current_and_run_next();
+        Trap::Exception(exception) => {
+            // any other exception here is caused by the application,
+            // as traps from the kernel itself go to `trap_from_kernel`
+            println!(
+                "[kernel] {:?} in application, scause = {:#x}, stval = {:#x}, sepc = {:#x}, kernel killed it.",
+                exception,
+                scause.bits(),
+                stval,
+                current_trap_cx().sepc
+            );
+            exit_current_and_run_next(user_exception_exit_code(scause.code()));
         }
         Trap::Interrupt(Interrupt::SupervisorTimer) => {
-            set_next_trigger();
-            suspend_current_and_run_next();
+            check_timer();
+            if tick_expired() && tick_current_task() {
+                preempt_current_and_run_next();
+            }
         }
         _ => {
             panic!(
@@ -76,7 +128,48 @@ pub fn trap_handler(cx: &mut TrapContext) -> &mut TrapContext {
             );
         }
     }
-    cx
+    trap_return();
+}
+
+/// return to user space
+#[no_mangle]
+pub fn trap_return() -> ! {
+    set_user_trap_entry();
+    account_current_trap_return();
+    start_tick();
+    program_next_timer();
+    // lazily restore the FP state, only if the FPU holds someone else's
+    let trap_cx = current_trap_cx();
+    let fp_owner_expected = expected_fp_owner(trap_cx);
+    if fp_owner() != fp_owner_expected {
+        trap_cx.load_fp();
+        set_fp_owner(fp_owner_expected);
+    }
+    let trap_cx_ptr = TRAP_CONTEXT_BASE;
+    let user_satp = current_user_token();
+    extern "C" {
+        fn __alltraps();
+        fn __restore();
+    }
+    let restore_va = __restore as usize - __alltraps as usize + TRAMPOLINE;
+    unsafe {
+        asm!(
+            "fence.i",
+            "jr {restore_va}",             // jump to new addr of __restore asm function
+            restore_va = in(reg) restore_va,
+            in("a0") trap_cx_ptr,      // a0 = virt addr of Trap Context
+            in("a1") user_satp,        // a1 = phy addr of usr page table
+            options(noreturn)
+        );
+    }
+}
+
+/// handle trap from kernel
+#[no_mangle]
+pub fn trap_from_kernel() -> ! {
+    use riscv::register::sepc;
+    trace!("stval = {:#x}, sepc = {:#x}", stval::read(), sepc::read());
+    panic!("a trap {:?} from kernel!", scause::read().cause());
 }
 
 pub use context::TrapContext;